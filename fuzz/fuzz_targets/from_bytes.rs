@@ -0,0 +1,9 @@
+#![no_main]
+
+use geotiff_rs::TiffFile;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // malformed files must be rejected with an error, never with a panic
+    let _ = TiffFile::from_bytes(data);
+});
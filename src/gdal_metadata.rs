@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+
+use super::{
+    parser::{tags::TAG_GDAL_METADATA, Ifd},
+    TiffParserError,
+};
+
+/// The metadata items of the dataset or of a single band, grouped by domain.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetadataItems {
+    items: BTreeMap<String, String>,
+    domains: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl MetadataItems {
+    /// Returns the value of the item with the given name in the default domain.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.items.get(name).map(String::as_str)
+    }
+
+    /// The items of the default domain, by name.
+    pub fn items(&self) -> &BTreeMap<String, String> {
+        &self.items
+    }
+
+    /// Returns the items of the named domain, such as `IMAGERY` or `RPC`.
+    pub fn domain(&self, domain: &str) -> Option<&BTreeMap<String, String>> {
+        self.domains.get(domain)
+    }
+
+    /// The names of the domains other than the default one.
+    pub fn domains(&self) -> impl Iterator<Item = &str> {
+        self.domains.keys().map(String::as_str)
+    }
+
+    fn insert(&mut self, domain: Option<&str>, name: &str, value: &str) {
+        let items = match domain {
+            None | Some("") => &mut self.items,
+            Some(domain) => self.domains.entry(domain.to_string()).or_default(),
+        };
+        items.insert(name.to_string(), value.to_string());
+    }
+}
+
+/// The metadata GDAL stores as XML in the GDAL_METADATA tag, such as band descriptions, scales
+/// and offsets, units and statistics.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GdalMetadata {
+    dataset: MetadataItems,
+    bands: BTreeMap<usize, MetadataItems>,
+}
+
+impl GdalMetadata {
+    /// Parses the XML document stored in the GDAL_METADATA tag, which looks like:
+    ///
+    /// ```xml
+    /// <GDALMetadata>
+    ///   <Item name="AREA_OR_POINT">Area</Item>
+    ///   <Item name="SCALE" sample="0" role="scale">0.01</Item>
+    ///   <Item name="CLOUD_COVER" domain="IMAGERY">12</Item>
+    /// </GDALMetadata>
+    /// ```
+    pub fn parse(xml: &str) -> Result<Self, TiffParserError> {
+        let document = roxmltree::Document::parse(xml)?;
+        let mut metadata = GdalMetadata::default();
+        for item in document
+            .root_element()
+            .children()
+            .filter(|node| node.has_tag_name("Item"))
+        {
+            let Some(name) = item.attribute("name") else {
+                continue;
+            };
+            let value = item.text().unwrap_or("");
+            let domain = item.attribute("domain");
+            match item.attribute("sample") {
+                Some(sample) => {
+                    let Ok(band) = sample.trim().parse::<usize>() else {
+                        continue;
+                    };
+                    metadata
+                        .bands
+                        .entry(band)
+                        .or_default()
+                        .insert(domain, name, value);
+                }
+                None => metadata.dataset.insert(domain, name, value),
+            }
+        }
+        Ok(metadata)
+    }
+
+    pub(crate) fn from_ifd(ifd: &Ifd) -> Result<Self, TiffParserError> {
+        Self::parse(ifd.get_value(TAG_GDAL_METADATA)?.ascii()?)
+    }
+
+    /// The items applying to the whole dataset.
+    pub fn dataset(&self) -> &MetadataItems {
+        &self.dataset
+    }
+
+    /// Returns the items of the given band, counted from 0.
+    pub fn band(&self, band: usize) -> Option<&MetadataItems> {
+        self.bands.get(&band)
+    }
+
+    /// The bands with metadata and their items, ordered by band.
+    pub fn bands(&self) -> impl Iterator<Item = (usize, &MetadataItems)> {
+        self.bands.iter().map(|(band, items)| (*band, items))
+    }
+
+    /// The number of bands with metadata, which may be less than the number of bands of the
+    /// image.
+    pub fn num_bands(&self) -> usize {
+        self.bands.len()
+    }
+
+    /// The description of the given band.
+    pub fn description(&self, band: usize) -> Option<&str> {
+        self.band(band)?.get("DESCRIPTION")
+    }
+
+    /// The unit of the physical values of the given band, such as `m` or `K`.
+    pub fn unit_type(&self, band: usize) -> Option<&str> {
+        self.band(band)?.get("UNITTYPE")
+    }
+
+    /// The factor by which the raw samples of the given band are multiplied to get physical
+    /// values.
+    pub fn scale(&self, band: usize) -> Option<f64> {
+        self.band_number(band, "SCALE")
+    }
+
+    /// The value added to the scaled samples of the given band to get physical values.
+    pub fn offset(&self, band: usize) -> Option<f64> {
+        self.band_number(band, "OFFSET")
+    }
+
+    /// Converts a raw sample of the given band to a physical value, as `value * scale + offset`.
+    /// Bands without a scale or offset use 1 and 0 respectively.
+    pub fn apply_scale_offset(&self, band: usize, value: f64) -> f64 {
+        value * self.scale(band).unwrap_or(1.0) + self.offset(band).unwrap_or(0.0)
+    }
+
+    fn band_number(&self, band: usize, name: &str) -> Option<f64> {
+        self.band(band)?.get(name)?.trim().parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn band_items() {
+        let metadata = GdalMetadata::parse(
+            r#"<GDALMetadata>
+                <Item name="AREA_OR_POINT">Area</Item>
+                <Item name="SCALE" sample="2" role="scale">0.01</Item>
+                <Item name="DESCRIPTION" sample="18446744073709551615">Last</Item>
+                <Item name="CLOUD_COVER" domain="IMAGERY">12</Item>
+            </GDALMetadata>"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.dataset().get("AREA_OR_POINT"), Some("Area"));
+        assert_eq!(
+            metadata.dataset().domain("IMAGERY").unwrap()["CLOUD_COVER"],
+            "12"
+        );
+        assert_eq!(metadata.num_bands(), 2);
+        assert!(metadata.band(0).is_none());
+        assert_eq!(metadata.scale(2), Some(0.01));
+        assert_eq!(metadata.description(usize::MAX), Some("Last"));
+        assert_eq!(
+            metadata.bands().map(|(band, _)| band).collect::<Vec<_>>(),
+            [2, usize::MAX]
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use super::{
+    parser::{tags::*, Ifd},
+    TiffParserError,
+};
+
+const GEO_TIFF_TAG_LOCATION_NONE: u16 = 0;
+
+macro_rules! geo_keys {
+    ($($name:ident = $code:expr,)*) => {
+        /// A key stored in the GeoKeyDirectoryTag.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum GeoKey {
+            $($name,)*
+            Unknown(u16),
+        }
+
+        impl GeoKey {
+            pub fn from_u16(code: u16) -> Self {
+                match code {
+                    $($code => GeoKey::$name,)*
+                    code => GeoKey::Unknown(code),
+                }
+            }
+
+            pub fn code(&self) -> u16 {
+                match self {
+                    $(GeoKey::$name => $code,)*
+                    GeoKey::Unknown(code) => *code,
+                }
+            }
+        }
+    };
+}
+
+geo_keys! {
+    GTModelType = 1024,
+    GTRasterType = 1025,
+    GTCitation = 1026,
+
+    GeographicType = 2048,
+    GeogCitation = 2049,
+    GeogGeodeticDatum = 2050,
+    GeogPrimeMeridian = 2051,
+    GeogLinearUnits = 2052,
+    GeogLinearUnitSize = 2053,
+    GeogAngularUnits = 2054,
+    GeogAngularUnitSize = 2055,
+    GeogEllipsoid = 2056,
+    GeogSemiMajorAxis = 2057,
+    GeogSemiMinorAxis = 2058,
+    GeogInvFlattening = 2059,
+    GeogAzimuthUnits = 2060,
+    GeogPrimeMeridianLong = 2061,
+
+    ProjectedCSType = 3072,
+    PCSCitation = 3073,
+    Projection = 3074,
+    ProjCoordTrans = 3075,
+    ProjLinearUnits = 3076,
+    ProjLinearUnitSize = 3077,
+    ProjStdParallel1 = 3078,
+    ProjStdParallel2 = 3079,
+    ProjNatOriginLong = 3080,
+    ProjNatOriginLat = 3081,
+    ProjFalseEasting = 3082,
+    ProjFalseNorthing = 3083,
+    ProjFalseOriginLong = 3084,
+    ProjFalseOriginLat = 3085,
+    ProjFalseOriginEasting = 3086,
+    ProjFalseOriginNorthing = 3087,
+    ProjCenterLong = 3088,
+    ProjCenterLat = 3089,
+    ProjCenterEasting = 3090,
+    ProjCenterNorthing = 3091,
+    ProjScaleAtNatOrigin = 3092,
+    ProjScaleAtCenter = 3093,
+    ProjAzimuthAngle = 3094,
+    ProjStraightVertPoleLong = 3095,
+
+    VerticalCSType = 4096,
+    VerticalCitation = 4097,
+    VerticalDatum = 4098,
+    VerticalUnits = 4099,
+}
+
+/// The value of a GeoKey, resolved from wherever the directory says it is stored.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeoKeyValue {
+    Short(u16),
+    Shorts(Vec<u16>),
+    Doubles(Vec<f64>),
+    Ascii(String),
+}
+
+impl GeoKeyValue {
+    pub fn short(&self) -> Option<u16> {
+        match self {
+            GeoKeyValue::Short(val) => Some(*val),
+            GeoKeyValue::Shorts(vals) if vals.len() == 1 => Some(vals[0]),
+            _ => None,
+        }
+    }
+
+    pub fn double(&self) -> Option<f64> {
+        match self {
+            GeoKeyValue::Doubles(vals) if vals.len() == 1 => Some(vals[0]),
+            _ => None,
+        }
+    }
+
+    pub fn ascii(&self) -> Option<&str> {
+        match self {
+            GeoKeyValue::Ascii(string) => Some(string),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GeoKeyDirectory {
+    pub version: u16,
+    pub key_revision: u16,
+    pub minor_revision: u16,
+    pub keys: Vec<(GeoKey, GeoKeyValue)>,
+}
+
+impl GeoKeyDirectory {
+    pub(crate) fn from_ifd(ifd: &Ifd) -> Result<Self, TiffParserError> {
+        let directory_value = ifd.get_value(TAG_GEO_KEY_DIRECTORY)?;
+        let directory = directory_value.shorts()?;
+        if directory.len() < 4 {
+            return Err(TiffParserError::InvalidValue(
+                directory_value.clone(),
+                "GeoKeyDirectory header too short",
+            ));
+        }
+        let num_keys = directory[3] as usize;
+        if directory.len() < 4 * (num_keys + 1) {
+            return Err(TiffParserError::InvalidValue(
+                directory_value.clone(),
+                "GeoKeyDirectory shorter than its number of keys",
+            ));
+        }
+
+        let doubles = match ifd.get_value(TAG_GEO_DOUBLE_PARAMS) {
+            Ok(value) => value.doubles()?,
+            Err(_) => &[],
+        };
+        let ascii = match ifd.get_value(TAG_GEO_ASCII_PARAMS) {
+            Ok(value) => value.ascii()?,
+            Err(_) => "",
+        };
+
+        let keys = directory[4..4 * (num_keys + 1)]
+            .chunks(4)
+            .map(|entry| {
+                let key = GeoKey::from_u16(entry[0]);
+                let location = entry[1];
+                let count = entry[2] as usize;
+                let value_offset = entry[3];
+                let value = match location {
+                    GEO_TIFF_TAG_LOCATION_NONE => GeoKeyValue::Short(value_offset),
+                    TAG_GEO_KEY_DIRECTORY => GeoKeyValue::Shorts(
+                        directory
+                            .get(value_offset as usize..value_offset as usize + count)
+                            .ok_or(TiffParserError::InvalidGeoKey(
+                                key.code(),
+                                "shorts out of range",
+                            ))?
+                            .to_vec(),
+                    ),
+                    TAG_GEO_DOUBLE_PARAMS => GeoKeyValue::Doubles(
+                        doubles
+                            .get(value_offset as usize..value_offset as usize + count)
+                            .ok_or(TiffParserError::InvalidGeoKey(
+                                key.code(),
+                                "doubles out of range",
+                            ))?
+                            .to_vec(),
+                    ),
+                    TAG_GEO_ASCII_PARAMS => {
+                        let string = ascii
+                            .get(value_offset as usize..value_offset as usize + count)
+                            .ok_or(TiffParserError::InvalidGeoKey(
+                                key.code(),
+                                "ascii out of range",
+                            ))?;
+                        // strings in GeoAsciiParamsTag are terminated by a '|' instead of a null
+                        GeoKeyValue::Ascii(string.split('|').next().unwrap_or_default().to_owned())
+                    }
+                    _ => {
+                        return Err(TiffParserError::InvalidGeoKey(
+                            key.code(),
+                            "unknown tag location",
+                        ));
+                    }
+                };
+                Ok((key, value))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(GeoKeyDirectory {
+            version: directory[0],
+            key_revision: directory[1],
+            minor_revision: directory[2],
+            keys,
+        })
+    }
+
+    pub fn get(&self, key: GeoKey) -> Option<&GeoKeyValue> {
+        self.keys
+            .iter()
+            .find(|(entry_key, _)| *entry_key == key)
+            .map(|(_, value)| value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{
+        test_tiff::{write_tiff, TestIfd},
+        TiffFile,
+    };
+
+    fn directory(
+        keys: &[[u16; 4]],
+        doubles: &[f64],
+        ascii: &str,
+    ) -> Result<GeoKeyDirectory, TiffParserError> {
+        let mut directory = vec![1, 1, 0, keys.len() as u16];
+        directory.extend(keys.iter().flatten());
+        // a value stored in the directory itself, after the keys
+        directory.push(9001);
+        let ifd = TestIfd::stripped(1, 1, 1)
+            .short(TAG_GEO_KEY_DIRECTORY, &directory)
+            .double(TAG_GEO_DOUBLE_PARAMS, doubles)
+            .ascii(TAG_GEO_ASCII_PARAMS, ascii)
+            .strips(vec![vec![0]]);
+        let tiff = TiffFile::from_bytes(&write_tiff(&[ifd])).unwrap();
+        GeoKeyDirectory::from_ifd(&tiff.ifds()[0])
+    }
+
+    #[test]
+    fn values_of_every_location() {
+        let keys = [
+            [1024, 0, 1, 2],
+            [1026, TAG_GEO_ASCII_PARAMS, 7, 0],
+            [2057, TAG_GEO_DOUBLE_PARAMS, 1, 1],
+            [3073, TAG_GEO_ASCII_PARAMS, 6, 7],
+            [4099, TAG_GEO_KEY_DIRECTORY, 1, 28],
+            [5000, 0, 1, 7],
+        ];
+        let directory = directory(&keys, &[0.0, 6378137.0], "WGS 84|NAD27|").unwrap();
+        assert_eq!(
+            (
+                directory.version,
+                directory.key_revision,
+                directory.minor_revision
+            ),
+            (1, 1, 0)
+        );
+        assert_eq!(directory.keys.len(), 6);
+        assert_eq!(directory.get(GeoKey::GTModelType).unwrap().short(), Some(2));
+        assert_eq!(
+            directory.get(GeoKey::GTCitation).unwrap().ascii(),
+            Some("WGS 84")
+        );
+        assert_eq!(
+            directory.get(GeoKey::GeogSemiMajorAxis).unwrap().double(),
+            Some(6378137.0)
+        );
+        assert_eq!(
+            directory.get(GeoKey::PCSCitation).unwrap().ascii(),
+            Some("NAD27")
+        );
+        assert_eq!(
+            directory.get(GeoKey::VerticalUnits),
+            Some(&GeoKeyValue::Shorts(vec![9001]))
+        );
+        assert_eq!(
+            directory.get(GeoKey::Unknown(5000)),
+            Some(&GeoKeyValue::Short(7))
+        );
+        assert_eq!(GeoKey::Unknown(5000).code(), 5000);
+        assert_eq!(GeoKey::from_u16(3072), GeoKey::ProjectedCSType);
+    }
+
+    #[test]
+    fn invalid_directories() {
+        assert!(matches!(
+            directory(&[[2057, TAG_GEO_DOUBLE_PARAMS, 1, 2]], &[0.0, 1.0], "|"),
+            Err(TiffParserError::InvalidGeoKey(2057, "doubles out of range"))
+        ));
+        assert!(matches!(
+            directory(&[[1026, TAG_GEO_ASCII_PARAMS, 4, 0]], &[0.0], "|"),
+            Err(TiffParserError::InvalidGeoKey(1026, "ascii out of range"))
+        ));
+        assert!(matches!(
+            directory(&[[1024, 1234, 1, 0]], &[0.0], "|"),
+            Err(TiffParserError::InvalidGeoKey(1024, "unknown tag location"))
+        ));
+    }
+
+    #[test]
+    fn directory_shorter_than_its_keys() {
+        let ifd = TestIfd::stripped(1, 1, 1)
+            .short(TAG_GEO_KEY_DIRECTORY, &[1, 1, 0, 2, 1024, 0, 1, 2])
+            .strips(vec![vec![0]]);
+        let tiff = TiffFile::from_bytes(&write_tiff(&[ifd])).unwrap();
+        assert!(matches!(
+            GeoKeyDirectory::from_ifd(&tiff.ifds()[0]),
+            Err(TiffParserError::InvalidValue(_, _))
+        ));
+    }
+}
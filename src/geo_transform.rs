@@ -0,0 +1,228 @@
+use super::{
+    parser::{tags::*, Ifd},
+    GeoKey, GeoKeyDirectory, TiffParserError,
+};
+
+const RASTER_PIXEL_IS_AREA: u16 = 1;
+const RASTER_PIXEL_IS_POINT: u16 = 2;
+
+/// An affine transformation between raster space and model space.
+///
+/// Raster coordinates are continuous: `(0.0, 0.0)` is the top-left corner of the top-left pixel,
+/// and the center of the pixel in column `i` and row `j` is at `(i + 0.5, j + 0.5)`, regardless
+/// of whether the file declares PixelIsArea or PixelIsPoint.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoTransform {
+    /// The coefficients in the same order as GDAL's geotransform: `x = c[0] + c[1] * col +
+    /// c[2] * row` and `y = c[3] + c[4] * col + c[5] * row`.
+    pub coefficients: [f64; 6],
+    inverse: [f64; 6],
+}
+
+impl GeoTransform {
+    pub fn new(coefficients: [f64; 6]) -> Result<Self, TiffParserError> {
+        let [x0, a, b, y0, d, e] = coefficients;
+        let det = a * e - b * d;
+        if det == 0.0 || !det.is_finite() {
+            return Err(TiffParserError::InvalidGeoreferencing(
+                "the transformation is not invertible",
+            ));
+        }
+        let (ia, ib, id, ie) = (e / det, -b / det, -d / det, a / det);
+        let inverse = [-ia * x0 - ib * y0, ia, ib, -id * x0 - ie * y0, id, ie];
+        Ok(Self {
+            coefficients,
+            inverse,
+        })
+    }
+
+    pub(crate) fn from_ifd(
+        ifd: &Ifd,
+        geo_key_directory: Option<&GeoKeyDirectory>,
+    ) -> Result<Self, TiffParserError> {
+        let pixel_scale = ifd
+            .get_value(TAG_MODEL_PIXEL_SCALE)
+            .ok()
+            .map(|value| value.doubles())
+            .transpose()?;
+        let tiepoints = ifd
+            .get_value(TAG_MODEL_TIEPOINT)
+            .ok()
+            .map(|value| value.doubles())
+            .transpose()?;
+        let transformation = ifd
+            .get_value(TAG_MODEL_TRANSFORMATION)
+            .ok()
+            .map(|value| value.doubles())
+            .transpose()?;
+
+        // the coefficients of the transformation as declared in the file, in which integer raster
+        // coordinates may refer either to pixel corners or to pixel centers
+        let [x0, a, b, y0, d, e] = match (pixel_scale, tiepoints, transformation) {
+            (None, None, None) => {
+                return Err(TiffParserError::MissingGeoreferencing);
+            }
+            (_, _, Some(matrix)) => {
+                if pixel_scale.is_some() || tiepoints.is_some() {
+                    return Err(TiffParserError::InvalidGeoreferencing(
+                        "ModelTransformation can't be combined with ModelPixelScale or ModelTiepoint",
+                    ));
+                }
+                if matrix.len() != 16 {
+                    return Err(TiffParserError::InvalidGeoreferencing(
+                        "ModelTransformation must contain 16 values",
+                    ));
+                }
+                [
+                    matrix[3], matrix[0], matrix[1], matrix[7], matrix[4], matrix[5],
+                ]
+            }
+            (Some(scale), Some(tiepoints), None) => {
+                if scale.len() < 2 {
+                    return Err(TiffParserError::InvalidGeoreferencing(
+                        "ModelPixelScale must contain at least 2 values",
+                    ));
+                }
+                if tiepoints.len() != 6 {
+                    return Err(TiffParserError::InvalidGeoreferencing(
+                        "ModelPixelScale requires exactly one ModelTiepoint",
+                    ));
+                }
+                let (i, j, x, y) = (tiepoints[0], tiepoints[1], tiepoints[3], tiepoints[4]);
+                let (sx, sy) = (scale[0], scale[1]);
+                [x - i * sx, sx, 0.0, y + j * sy, 0.0, -sy]
+            }
+            (None, Some(_), None) => {
+                return Err(TiffParserError::InvalidGeoreferencing(
+                    "ModelTiepoint without ModelPixelScale is not an affine transformation",
+                ));
+            }
+            (Some(_), None, None) => {
+                return Err(TiffParserError::InvalidGeoreferencing(
+                    "ModelPixelScale without ModelTiepoint",
+                ));
+            }
+        };
+
+        let raster_type = geo_key_directory
+            .and_then(|directory| directory.get(GeoKey::GTRasterType))
+            .map(|value| {
+                value.short().ok_or(TiffParserError::InvalidGeoKey(
+                    GeoKey::GTRasterType.code(),
+                    "expected a short",
+                ))
+            })
+            .transpose()?
+            .unwrap_or(RASTER_PIXEL_IS_AREA);
+
+        match raster_type {
+            RASTER_PIXEL_IS_AREA => Self::new([x0, a, b, y0, d, e]),
+            // integer raster coordinates refer to pixel centers, so shift by half a pixel
+            RASTER_PIXEL_IS_POINT => {
+                Self::new([x0 - 0.5 * (a + b), a, b, y0 - 0.5 * (d + e), d, e])
+            }
+            _ => Err(TiffParserError::InvalidGeoKey(
+                GeoKey::GTRasterType.code(),
+                "unknown raster type",
+            )),
+        }
+    }
+
+    /// Converts raster coordinates (column, row) to model coordinates (x, y).
+    pub fn pixel_to_model(&self, col: f64, row: f64) -> (f64, f64) {
+        apply(&self.coefficients, col, row)
+    }
+
+    /// Converts model coordinates (x, y) to raster coordinates (column, row).
+    pub fn model_to_pixel(&self, x: f64, y: f64) -> (f64, f64) {
+        apply(&self.inverse, x, y)
+    }
+}
+
+fn apply(c: &[f64; 6], u: f64, v: f64) -> (f64, f64) {
+    (c[0] + c[1] * u + c[2] * v, c[3] + c[4] * u + c[5] * v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        parser::{
+            test_tiff::{write_tiff, TestIfd},
+            TiffFile,
+        },
+        GeoKeyValue,
+    };
+
+    fn geo_transform(
+        ifd: TestIfd,
+        geo_key_directory: Option<&GeoKeyDirectory>,
+    ) -> Result<GeoTransform, TiffParserError> {
+        let tiff = TiffFile::from_bytes(&write_tiff(&[ifd.strips(vec![vec![0]])])).unwrap();
+        GeoTransform::from_ifd(&tiff.ifds()[0], geo_key_directory)
+    }
+
+    fn scale_and_tiepoint() -> TestIfd {
+        TestIfd::stripped(1, 1, 1)
+            .double(TAG_MODEL_PIXEL_SCALE, &[10.0, 20.0, 0.0])
+            .double(TAG_MODEL_TIEPOINT, &[1.0, 2.0, 0.0, 100.0, 200.0, 0.0])
+    }
+
+    #[test]
+    fn pixel_scale_and_tiepoint() {
+        let transform = geo_transform(scale_and_tiepoint(), None).unwrap();
+        assert_eq!(transform.coefficients, [90.0, 10.0, 0.0, 240.0, 0.0, -20.0]);
+        assert_eq!(transform.pixel_to_model(1.0, 2.0), (100.0, 200.0));
+        assert_eq!(transform.model_to_pixel(100.0, 200.0), (1.0, 2.0));
+    }
+
+    #[test]
+    fn model_transformation() {
+        let matrix = [
+            2.0, 1.0, 0.0, 10.0, 0.5, -4.0, 0.0, 20.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+        ];
+        let ifd = TestIfd::stripped(1, 1, 1).double(TAG_MODEL_TRANSFORMATION, &matrix);
+        let transform = geo_transform(ifd, None).unwrap();
+        assert_eq!(transform.coefficients, [10.0, 2.0, 1.0, 20.0, 0.5, -4.0]);
+        assert_eq!(transform.pixel_to_model(1.0, 1.0), (13.0, 16.5));
+        let (col, row) = transform.model_to_pixel(13.0, 16.5);
+        assert!((col - 1.0).abs() < 1e-12 && (row - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn pixel_is_point_is_shifted_by_half_a_pixel() {
+        let directory = GeoKeyDirectory {
+            version: 1,
+            key_revision: 1,
+            minor_revision: 0,
+            keys: vec![(
+                GeoKey::GTRasterType,
+                GeoKeyValue::Short(RASTER_PIXEL_IS_POINT),
+            )],
+        };
+        let transform = geo_transform(scale_and_tiepoint(), Some(&directory)).unwrap();
+        assert_eq!(transform.coefficients, [85.0, 10.0, 0.0, 250.0, 0.0, -20.0]);
+        // the tiepoint is at the center of its pixel
+        assert_eq!(transform.pixel_to_model(1.5, 2.5), (100.0, 200.0));
+    }
+
+    #[test]
+    fn invalid_georeferencing() {
+        let contradictory = scale_and_tiepoint().double(TAG_MODEL_TRANSFORMATION, &[0.0; 16]);
+        assert!(matches!(
+            geo_transform(contradictory, None),
+            Err(TiffParserError::InvalidGeoreferencing(_))
+        ));
+        assert!(matches!(
+            geo_transform(TestIfd::stripped(1, 1, 1), None),
+            Err(TiffParserError::MissingGeoreferencing)
+        ));
+        let singular = TestIfd::stripped(1, 1, 1)
+            .double(TAG_MODEL_PIXEL_SCALE, &[0.0, 1.0, 0.0])
+            .double(TAG_MODEL_TIEPOINT, &[0.0; 6]);
+        assert!(matches!(
+            geo_transform(singular, None),
+            Err(TiffParserError::InvalidGeoreferencing(_))
+        ));
+    }
+}
@@ -1,23 +1,801 @@
-use std::path::Path;
+use std::{
+    fs::File,
+    io::BufReader,
+    path::Path,
+    sync::{Mutex, OnceLock},
+};
 
-use super::{TiffFile, TiffParserError};
+use super::{
+    mask::find_mask, parser::tags::TAG_GDAL_NODATA, ByteSource, GdalMetadata, GeoKeyDirectory,
+    GeoTransform, Ifd, Interpolation, Overview, RasterBuffer, RasterValue, Sample, TiffOptions,
+    TiffParserError, TiffReader,
+};
 
+/// The `ByteSource` of a `GeoTiff`, whose type isn't exposed.
+struct BoxedSource(Box<dyn ByteSource + Send>);
+
+impl ByteSource for BoxedSource {
+    fn read_range(&mut self, offset: u64, len: usize) -> Result<Vec<u8>, TiffParserError> {
+        self.0.read_range(offset, len)
+    }
+}
+
+/// A GeoTIFF file. Only the metadata of the first image is read when the file is opened, its
+/// pixels are decoded when they are requested.
 #[derive(Debug)]
 pub struct GeoTiff {
-    tiff: TiffFile,
+    reader: Mutex<TiffReader<BoxedSource>>,
+    /// The index of the IFD of the full-resolution image.
+    image: usize,
+    overviews: Vec<Overview>,
+    /// The index of the IFD of the transparency mask of the image, if it has one.
+    mask: Option<usize>,
+    width: usize,
+    height: usize,
+    num_bands: usize,
+    geo_key_directory: Result<GeoKeyDirectory, TiffParserError>,
+    geo_transform: Result<GeoTransform, TiffParserError>,
+    nodata: Option<RasterValue>,
+    gdal_metadata: Result<GdalMetadata, TiffParserError>,
+    data: OnceLock<Option<RasterBuffer>>,
 }
 
 impl GeoTiff {
     pub fn from_file<P: AsRef<Path>>(name: P) -> Result<Self, TiffParserError> {
-        let tiff = TiffFile::from_file(name)?;
+        Self::from_file_with_options(name, &TiffOptions::default())
+    }
+
+    pub fn from_file_with_options<P: AsRef<Path>>(
+        name: P,
+        options: &TiffOptions,
+    ) -> Result<Self, TiffParserError> {
+        Self::from_source(BufReader::new(File::open(name)?), options)
+    }
+
+    /// Reads a GeoTIFF from any `ByteSource`, such as a Cloud Optimized GeoTIFF on a remote
+    /// server, only fetching the parts of the file that are needed.
+    pub fn from_source<B: ByteSource + Send + 'static>(
+        source: B,
+        options: &TiffOptions,
+    ) -> Result<Self, TiffParserError> {
+        let reader = TiffReader::with_options(BoxedSource(Box::new(source)), options.clone())?;
+        if reader.ifds().is_empty() {
+            return Err(TiffParserError::MissingIfd(0));
+        }
+        // the full-resolution image normally comes first, but skip any overviews and masks
+        // that might precede it
+        let image = reader
+            .ifds()
+            .iter()
+            .position(|ifd| !ifd.is_reduced_resolution() && !ifd.is_mask())
+            .unwrap_or(0);
+        let related = related_ifds(&reader, image);
+        let overviews = Overview::find(&related, image);
+        let mask = find_mask(&related, image);
+        let ifd = &reader.ifds()[image];
+        // georeferencing which can't be interpreted, such as ground control points, and
+        // metadata which isn't valid XML don't prevent the pixels from being read, so the errors
+        // are kept for the getters
+        let geo_key_directory = GeoKeyDirectory::from_ifd(ifd);
+        let geo_transform = GeoTransform::from_ifd(ifd, geo_key_directory.as_ref().ok());
+        let gdal_metadata = GdalMetadata::from_ifd(ifd);
+
+        let nodata = ifd
+            .get_value(TAG_GDAL_NODATA)
+            .ok()
+            .and_then(|value| value.ascii().ok())
+            .and_then(|string| ifd.parse_sample(string));
+
+        Ok(Self {
+            width: ifd.image_width().unwrap_or(0) as usize,
+            height: ifd.image_length().unwrap_or(0) as usize,
+            num_bands: ifd.samples_per_pixel().unwrap_or(1) as usize,
+            mask,
+            reader: Mutex::new(reader),
+            image,
+            overviews,
+            geo_key_directory,
+            geo_transform,
+            nodata,
+            gdal_metadata,
+            data: OnceLock::new(),
+        })
+    }
+
+    /// The GeoKeys of the image, or the error met while reading them, which is
+    /// `MissingValue` if the image has none.
+    pub fn geo_key_directory(&self) -> Result<&GeoKeyDirectory, &TiffParserError> {
+        self.geo_key_directory.as_ref()
+    }
+
+    /// The transformation from raster to model coordinates, or the error met while reading it.
+    /// The error is `MissingGeoreferencing` if the image isn't georeferenced, and
+    /// `InvalidGeoreferencing` if its georeferencing is contradictory or isn't an affine
+    /// transformation, such as a set of ground control points.
+    pub fn geo_transform(&self) -> Result<&GeoTransform, &TiffParserError> {
+        self.geo_transform.as_ref()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn num_bands(&self) -> usize {
+        self.num_bands
+    }
+
+    /// The decoded samples of the whole image, with the bands of each pixel next to each other.
+    /// The image is decoded on the first call, `None` is returned if that fails.
+    pub fn data(&self) -> Option<&RasterBuffer> {
+        self.data
+            .get_or_init(|| self.lock_reader().read_image(self.image).ok().flatten())
+            .as_ref()
+    }
+
+    /// Decodes the samples of the window of `width` by `height` pixels whose top-left corner is
+    /// at column `x_off` and row `y_off`, with the bands of each pixel next to each other. Only
+    /// the strips or tiles intersecting the window are decoded.
+    pub fn read_window(
+        &self,
+        x_off: usize,
+        y_off: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<RasterBuffer, TiffParserError> {
+        self.lock_reader()
+            .read_window(self.image, x_off, y_off, width, height)
+    }
+
+    /// Like `read_window`, but only decodes the samples of the given band. If the bands are
+    /// stored in separate planes, the other bands aren't read at all.
+    pub fn read_band_window(
+        &self,
+        band: usize,
+        x_off: usize,
+        y_off: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<RasterBuffer, TiffParserError> {
+        self.lock_reader()
+            .read_band_window(self.image, band, x_off, y_off, width, height)
+    }
+
+    /// Returns whether the image has a transparency mask, marking some of its pixels as
+    /// invalid.
+    pub fn has_mask(&self) -> bool {
+        self.mask.is_some()
+    }
+
+    /// Decodes the transparency mask of the window of `width` by `height` pixels whose top-left
+    /// corner is at column `x_off` and row `y_off`, with `true` for valid pixels and `false` for
+    /// masked ones. Returns `None` if the image has no mask.
+    pub fn read_mask_window(
+        &self,
+        x_off: usize,
+        y_off: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<Option<Vec<bool>>, TiffParserError> {
+        let mask = match self.mask {
+            Some(mask) => mask,
+            None => return Ok(None),
+        };
+        let window = self
+            .lock_reader()
+            .read_window(mask, x_off, y_off, width, height)?;
+        Ok(Some(mask_values(&window)))
+    }
+
+    /// Returns whether the pixel in column `x` and row `y` is valid according to the
+    /// transparency mask of the image. All pixels of images without a mask are valid, while
+    /// pixels whose mask can't be read are not.
+    pub fn is_valid(&self, x: usize, y: usize) -> bool {
+        match self.read_mask_window(x, y, 1, 1) {
+            Ok(Some(mask)) => mask[0],
+            Ok(None) => true,
+            Err(_) => false,
+        }
+    }
+
+    /// The reduced-resolution versions of the image, ordered from the most to the least
+    /// detailed.
+    pub fn overviews(&self) -> &[Overview] {
+        &self.overviews
+    }
+
+    /// Picks the least detailed overview which still has at least the resolution needed to
+    /// produce output pixels covering `scale` by `scale` pixels of the full-resolution image,
+    /// or returns `None` if the full-resolution image is needed.
+    pub fn best_overview(&self, scale: f64) -> Option<&Overview> {
+        Overview::best(&self.overviews, scale)
+    }
+
+    /// Decodes the window of `width` by `height` pixels of the full-resolution image whose
+    /// top-left corner is at column `x_off` and row `y_off`, resampled to `out_width` by
+    /// `out_height` pixels with nearest neighbour interpolation. The samples are read from the
+    /// best overview for the requested resolution.
+    pub fn read_window_scaled(
+        &self,
+        x_off: usize,
+        y_off: usize,
+        width: usize,
+        height: usize,
+        out_width: usize,
+        out_height: usize,
+    ) -> Result<RasterBuffer, TiffParserError> {
+        let window = (x_off, y_off, width, height);
+        let level = match self.scaled_overview(window, out_width, out_height)? {
+            Some(overview) => Level::overview(overview, overview.ifd),
+            None => Level::full(self, self.image),
+        };
+        self.resample(level, self.num_bands, window, out_width, out_height)
+    }
+
+    /// Like `read_mask_window`, but for the pixels returned by `read_window_scaled` with the
+    /// same arguments. The mask of the overview they are read from is used, or that of the
+    /// full-resolution image if the overview has none.
+    pub fn read_mask_window_scaled(
+        &self,
+        x_off: usize,
+        y_off: usize,
+        width: usize,
+        height: usize,
+        out_width: usize,
+        out_height: usize,
+    ) -> Result<Option<Vec<bool>>, TiffParserError> {
+        let window = (x_off, y_off, width, height);
+        let level = match self.scaled_overview(window, out_width, out_height)? {
+            Some(
+                overview @ Overview {
+                    mask: Some(mask), ..
+                },
+            ) => Level::overview(overview, *mask),
+            _ => match self.mask {
+                Some(mask) => Level::full(self, mask),
+                None => return Ok(None),
+            },
+        };
+        let mask = self.resample(level, 1, window, out_width, out_height)?;
+        Ok(Some(mask_values(&mask)))
+    }
+
+    /// Checks the window of a scaled read and picks the overview to read it from.
+    fn scaled_overview(
+        &self,
+        (x_off, y_off, width, height): (usize, usize, usize, usize),
+        out_width: usize,
+        out_height: usize,
+    ) -> Result<Option<&Overview>, TiffParserError> {
+        if x_off.checked_add(width).is_none_or(|end| end > self.width)
+            || y_off
+                .checked_add(height)
+                .is_none_or(|end| end > self.height)
+        {
+            return Err(TiffParserError::InvalidWindow(x_off, y_off, width, height));
+        }
+        let scale = (width as f64 / out_width as f64).min(height as f64 / out_height as f64);
+        Ok(self.best_overview(scale))
+    }
+
+    /// Reads the part of the given level covering the window of the full-resolution image and
+    /// resamples it to `out_width` by `out_height` pixels.
+    fn resample(
+        &self,
+        level: Level,
+        num_bands: usize,
+        (x_off, y_off, width, height): (usize, usize, usize, usize),
+        out_width: usize,
+        out_height: usize,
+    ) -> Result<RasterBuffer, TiffParserError> {
+        let Level {
+            ifd,
+            scale_x,
+            scale_y,
+            width: level_width,
+            height: level_height,
+        } = level;
+        // the window in the pixels of the chosen level
+        let x0 = ((x_off as f64 / scale_x).floor() as usize).min(level_width);
+        let y0 = ((y_off as f64 / scale_y).floor() as usize).min(level_height);
+        let x1 = (((x_off + width) as f64 / scale_x).ceil() as usize).clamp(x0, level_width);
+        let y1 = (((y_off + height) as f64 / scale_y).ceil() as usize).clamp(y0, level_height);
+        let window = self
+            .lock_reader()
+            .read_window(ifd, x0, y0, x1 - x0, y1 - y0)?;
+        if x1 == x0 || y1 == y0 {
+            return Ok(window);
+        }
+
+        // the pixel of the level containing the center of each output pixel
+        let level_index = |out: usize, off: usize, size: usize, out_size: usize, scale: f64| {
+            let full = off as f64 + (out as f64 + 0.5) * size as f64 / out_size as f64;
+            (full / scale).floor() as usize
+        };
+        let indices = (0..out_height).flat_map(|j| {
+            let row = level_index(j, y_off, height, out_height, scale_y).clamp(y0, y1 - 1) - y0;
+            (0..out_width).flat_map(move |i| {
+                let col = level_index(i, x_off, width, out_width, scale_x).clamp(x0, x1 - 1) - x0;
+                (0..num_bands).map(move |band| (row * (x1 - x0) + col) * num_bands + band)
+            })
+        });
+        Ok(window.select(indices))
+    }
+
+    /// The nodata value of the image, as stored in the GDAL_NODATA tag, in the sample type of
+    /// the image. It is `None` if the tag is absent or its value isn't representable in that
+    /// type.
+    pub fn nodata(&self) -> Option<RasterValue> {
+        self.nodata
+    }
+
+    /// Returns the value of the given band of the pixel in column `x` and row `y`, counted from
+    /// the top-left corner of the image, or `None` if the pixel doesn't exist, is masked by the
+    /// transparency mask of the image or holds the nodata value.
+    ///
+    /// Row 0 is the first row stored in the file, as in `read_window`.
+    pub fn get_pixel(&self, x: usize, y: usize, band: usize) -> Option<RasterValue> {
+        let (width, height, num_bands) = (self.width(), self.height(), self.num_bands());
+        if x >= width || y >= height || band >= num_bands || !self.is_valid(x, y) {
+            return None;
+        }
+        let value = match self.data.get() {
+            Some(data) => data.as_ref()?.get((y * width + x) * num_bands + band),
+            None => self.read_band_window(band, x, y, 1, 1).ok()?.get(0),
+        }?;
+        match self.nodata {
+            Some(nodata) if value.matches(&nodata) => None,
+            _ => Some(value),
+        }
+    }
+
+    /// Like `get_pixel`, but returns the value as the primitive type `T`, or `None` if the
+    /// samples of the image are of another type.
+    pub fn get_pixel_as<T: Sample>(&self, x: usize, y: usize, band: usize) -> Option<T> {
+        T::from_value(self.get_pixel(x, y, band)?)
+    }
+
+    /// The metadata GDAL stored in the file, such as band descriptions, scales and offsets, or
+    /// the error met while parsing it, which is `MissingValue` if the image has none.
+    pub fn gdal_metadata(&self) -> Result<&GdalMetadata, &TiffParserError> {
+        self.gdal_metadata.as_ref()
+    }
+
+    /// Like `get_pixel`, but converts the value to a physical value with the scale and offset of
+    /// the band from the GDAL metadata.
+    pub fn get_physical_value(&self, x: usize, y: usize, band: usize) -> Option<f64> {
+        let value = self.get_pixel(x, y, band)?.as_f64();
+        Some(self.apply_scale_offset(band, value))
+    }
+
+    /// Like `read_window`, but converts the samples to physical values with the scales and
+    /// offsets of the bands from the GDAL metadata. Nodata and masked samples are NaN.
+    pub fn read_window_physical(
+        &self,
+        x_off: usize,
+        y_off: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<Vec<f64>, TiffParserError> {
+        let window = self.read_window(x_off, y_off, width, height)?;
+        let mask = self.read_mask_window(x_off, y_off, width, height)?;
+        Ok((0..window.len())
+            .map(|index| {
+                let masked = mask
+                    .as_ref()
+                    .is_some_and(|mask| !mask[index / self.num_bands]);
+                match window.get(index) {
+                    Some(value)
+                        if !masked && !self.nodata.is_some_and(|nodata| value.matches(&nodata)) =>
+                    {
+                        self.apply_scale_offset(index % self.num_bands, value.as_f64())
+                    }
+                    _ => f64::NAN,
+                }
+            })
+            .collect())
+    }
+
+    /// Returns the value at the model coordinates `(x, y)`, computed with the given interpolation
+    /// method. Returns `None` if the file isn't georeferenced, if the point lies outside of the
+    /// raster, or if any of the pixels contributing to the value is nodata. Only the first band
+    /// is sampled.
+    pub fn sample(&self, x: f64, y: f64, interpolation: Interpolation) -> Option<f64> {
+        let (col, row) = self.geo_transform.as_ref().ok()?.model_to_pixel(x, y);
+        interpolation.interpolate(col, row, self.width(), self.height(), |i, j| {
+            self.get_pixel(i, j, 0).map(|value| value.as_f64())
+        })
+    }
+
+    fn apply_scale_offset(&self, band: usize, value: f64) -> f64 {
+        match &self.gdal_metadata {
+            Ok(metadata) => metadata.apply_scale_offset(band, value),
+            Err(_) => value,
+        }
+    }
 
-        Ok(Self { tiff })
+    fn lock_reader(&self) -> std::sync::MutexGuard<'_, TiffReader<BoxedSource>> {
+        // the reader holds no state that a panic while reading could leave inconsistent
+        self.reader
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
     }
+}
+
+/// An image of the pyramid of a `GeoTiff`, or its mask, from which scaled windows are read.
+struct Level {
+    ifd: usize,
+    /// The size of a pixel of the level, in pixels of the full-resolution image.
+    scale_x: f64,
+    scale_y: f64,
+    width: usize,
+    height: usize,
+}
+
+impl Level {
+    fn full(geotiff: &GeoTiff, ifd: usize) -> Self {
+        Level {
+            ifd,
+            scale_x: 1.0,
+            scale_y: 1.0,
+            width: geotiff.width,
+            height: geotiff.height,
+        }
+    }
+
+    fn overview(overview: &Overview, ifd: usize) -> Self {
+        Level {
+            ifd,
+            scale_x: overview.scale_x,
+            scale_y: overview.scale_y,
+            width: overview.width,
+            height: overview.height,
+        }
+    }
+}
+
+/// Converts the samples of a mask to `true` for valid pixels and `false` for masked ones.
+fn mask_values(mask: &RasterBuffer) -> Vec<bool> {
+    (0..mask.len())
+        .map(|index| mask.get(index).is_some_and(|value| value.as_f64() != 0.0))
+        .collect()
+}
+
+/// The IFDs which may hold the overviews and masks of the image in IFD `image`, paired with
+/// their indices: GDAL writes them as top-level IFDs, while other writers store them as SubIFDs
+/// of the image.
+fn related_ifds<B: ByteSource>(reader: &TiffReader<B>, image: usize) -> Vec<(usize, &Ifd)> {
+    let mut indices = (0..reader.ifds().len()).collect::<Vec<_>>();
+    let mut parents = vec![image];
+    while let Some(parent) = parents.pop() {
+        let sub_ifds = reader.sub_ifds(parent);
+        indices.extend(&sub_ifds);
+        parents.extend(sub_ifds);
+    }
+    indices
+        .into_iter()
+        .filter_map(|index| Some((index, reader.ifd(index)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::parser::{
+        tags::{
+            TAG_BITS_PER_SAMPLE, TAG_GDAL_METADATA, TAG_MODEL_PIXEL_SCALE, TAG_MODEL_TIEPOINT,
+            TAG_MODEL_TRANSFORMATION, TAG_NEW_SUBFILE_TYPE, TAG_SAMPLE_FORMAT,
+        },
+        test_tiff::{write_tiff, TestIfd},
+    };
+
+    fn open(ifd: TestIfd) -> GeoTiff {
+        open_all(&[ifd])
+    }
+
+    fn open_all(ifds: &[TestIfd]) -> GeoTiff {
+        GeoTiff::from_source(Cursor::new(write_tiff(ifds)), &TiffOptions::default()).unwrap()
+    }
+
+    /// A 4 by 4 image with a 2 by 2 overview.
+    fn pyramid() -> [TestIfd; 2] {
+        [
+            TestIfd::stripped(4, 4, 4).strips(vec![(0..16).collect()]),
+            TestIfd::stripped(2, 2, 2)
+                .long(TAG_NEW_SUBFILE_TYPE, &[1])
+                .strips(vec![vec![100, 101, 102, 103]]),
+        ]
+    }
+
+    #[test]
+    fn pixel_rows_are_counted_from_the_top() {
+        let geotiff = open(TestIfd::stripped(2, 2, 1).strips(vec![vec![1, 2], vec![3, 4]]));
+        assert_eq!(geotiff.get_pixel(0, 0, 0), Some(RasterValue::U8(1)));
+        assert_eq!(geotiff.get_pixel(1, 0, 0), Some(RasterValue::U8(2)));
+        assert_eq!(geotiff.get_pixel(0, 1, 0), Some(RasterValue::U8(3)));
+        assert_eq!(geotiff.get_pixel(2, 0, 0), None);
+        // the same orientation as the decoded image and windows
+        assert_eq!(geotiff.data(), Some(&RasterBuffer::U8(vec![1, 2, 3, 4])));
+        assert_eq!(
+            geotiff.read_window(0, 1, 2, 1).unwrap(),
+            RasterBuffer::U8(vec![3, 4])
+        );
+    }
+
+    #[test]
+    fn ground_control_points_are_not_a_geo_transform() {
+        let tiepoints = [
+            0.0, 0.0, 0.0, 10.0, 20.0, 0.0, 2.0, 1.0, 0.0, 30.0, 10.0, 0.0,
+        ];
+        let geotiff = open(
+            TestIfd::stripped(2, 1, 1)
+                .double(TAG_MODEL_TIEPOINT, &tiepoints)
+                .strips(vec![vec![7, 8]]),
+        );
+        assert!(matches!(
+            geotiff.geo_transform(),
+            Err(TiffParserError::InvalidGeoreferencing(_))
+        ));
+        assert_eq!(geotiff.get_pixel(1, 0, 0), Some(RasterValue::U8(8)));
+    }
+
+    #[test]
+    fn invalid_metadata_is_an_error() {
+        let geotiff = open(
+            TestIfd::stripped(1, 1, 1)
+                .ascii(TAG_GDAL_METADATA, "<GDALMetadata><Item")
+                .strips(vec![vec![5]]),
+        );
+        assert!(matches!(
+            geotiff.gdal_metadata(),
+            Err(TiffParserError::GdalMetadata(_))
+        ));
+        assert_eq!(geotiff.get_pixel(0, 0, 0), Some(RasterValue::U8(5)));
+        assert_eq!(geotiff.get_physical_value(0, 0, 0), Some(5.0));
+
+        let geotiff = open(TestIfd::stripped(1, 1, 1).strips(vec![vec![5]]));
+        assert!(matches!(
+            geotiff.gdal_metadata(),
+            Err(TiffParserError::MissingValue(TAG_GDAL_METADATA))
+        ));
+    }
+
+    #[test]
+    fn contradictory_georeferencing_is_an_error() {
+        let geotiff = open(
+            TestIfd::stripped(1, 1, 1)
+                .double(TAG_MODEL_PIXEL_SCALE, &[1.0, 1.0, 0.0])
+                .double(TAG_MODEL_TIEPOINT, &[0.0; 6])
+                .double(TAG_MODEL_TRANSFORMATION, &[0.0; 16])
+                .strips(vec![vec![3]]),
+        );
+        assert!(matches!(
+            geotiff.geo_transform(),
+            Err(TiffParserError::InvalidGeoreferencing(_))
+        ));
+        assert!(matches!(
+            geotiff.geo_key_directory(),
+            Err(TiffParserError::MissingValue(_))
+        ));
+        assert_eq!(geotiff.sample(0.5, 0.5, Interpolation::Nearest), None);
+        assert_eq!(geotiff.get_pixel(0, 0, 0), Some(RasterValue::U8(3)));
+    }
+
+    #[test]
+    fn sample_by_model_coordinates() {
+        // 2 by 2 pixels of 1 by 1 units, with the top-left corner at the origin
+        let ifd = TestIfd::stripped(2, 2, 2)
+            .double(TAG_MODEL_PIXEL_SCALE, &[1.0, 1.0, 0.0])
+            .double(TAG_MODEL_TIEPOINT, &[0.0; 6]);
+        let geotiff = open(ifd.strips(vec![vec![10, 20, 30, 40]]));
+        assert_eq!(
+            geotiff.sample(0.5, -1.5, Interpolation::Nearest),
+            Some(30.0)
+        );
+        assert_eq!(
+            geotiff.sample(1.0, -1.0, Interpolation::Bilinear),
+            Some(25.0)
+        );
+        assert_eq!(
+            geotiff.sample(1.0, -1.0, Interpolation::Bicubic),
+            Some(25.0)
+        );
+        assert_eq!(geotiff.sample(2.5, -1.0, Interpolation::Nearest), None);
+        assert_eq!(geotiff.sample(1.0, 0.5, Interpolation::Nearest), None);
+
+        let ifd = TestIfd::stripped(2, 2, 2)
+            .double(TAG_MODEL_PIXEL_SCALE, &[1.0, 1.0, 0.0])
+            .double(TAG_MODEL_TIEPOINT, &[0.0; 6])
+            .ascii(TAG_GDAL_NODATA, "40");
+        let geotiff = open(ifd.strips(vec![vec![10, 20, 30, 40]]));
+        assert_eq!(
+            geotiff.sample(0.5, -0.5, Interpolation::Nearest),
+            Some(10.0)
+        );
+        assert_eq!(geotiff.sample(1.0, -1.0, Interpolation::Bilinear), None);
+    }
+
+    /// A 1-bit mask with the given NewSubfileType, whose rows are a byte each.
+    fn mask(width: u32, length: u32, subfile_type: u32, rows: Vec<u8>) -> TestIfd {
+        TestIfd::stripped(width, length, length)
+            .long(TAG_NEW_SUBFILE_TYPE, &[subfile_type])
+            .short(TAG_BITS_PER_SAMPLE, &[1])
+            .strips(vec![rows])
+    }
+
+    #[test]
+    fn overviews_in_sub_ifds() {
+        let [image, overview] = pyramid();
+        let geotiff = open_all(&[image.sub_ifd(overview)]);
+        assert_eq!(geotiff.overviews().len(), 1);
+        assert_eq!(geotiff.overviews()[0].ifd, 1);
+        assert_eq!(geotiff.overviews()[0].width, 2);
+        assert_eq!(
+            geotiff.read_window_scaled(0, 0, 4, 4, 2, 2).unwrap(),
+            RasterBuffer::U8(vec![100, 101, 102, 103])
+        );
+    }
+
+    #[test]
+    fn read_window_scaled() {
+        let geotiff = open_all(&pyramid());
+        assert_eq!(geotiff.best_overview(1.0), None);
+        assert_eq!(
+            geotiff.best_overview(2.0).map(|overview| overview.ifd),
+            Some(1)
+        );
+        // the full-resolution image
+        assert_eq!(
+            geotiff.read_window_scaled(1, 1, 2, 2, 2, 2).unwrap(),
+            RasterBuffer::U8(vec![5, 6, 9, 10])
+        );
+        assert_eq!(
+            geotiff.read_window_scaled(0, 0, 2, 1, 4, 2).unwrap(),
+            RasterBuffer::U8(vec![0, 0, 1, 1, 0, 0, 1, 1])
+        );
+        // the overview
+        assert_eq!(
+            geotiff.read_window_scaled(0, 0, 4, 4, 2, 2).unwrap(),
+            RasterBuffer::U8(vec![100, 101, 102, 103])
+        );
+        assert_eq!(
+            geotiff.read_window_scaled(2, 0, 2, 2, 1, 1).unwrap(),
+            RasterBuffer::U8(vec![101])
+        );
+        // the least detailed level is used below its resolution
+        assert_eq!(
+            geotiff.read_window_scaled(0, 0, 4, 4, 1, 1).unwrap(),
+            RasterBuffer::U8(vec![103])
+        );
+        assert!(matches!(
+            geotiff.read_window_scaled(2, 2, 3, 2, 1, 1),
+            Err(TiffParserError::InvalidWindow(2, 2, 3, 2))
+        ));
+    }
+
+    #[test]
+    fn masks_of_the_image_and_its_overview() {
+        let [image, overview] = pyramid();
+        let geotiff = open_all(&[
+            image,
+            mask(4, 4, 4, vec![0xf0, 0xf0, 0x30, 0xf0]),
+            overview,
+            mask(2, 2, 5, vec![0x80, 0xc0]),
+        ]);
+        assert!(geotiff.has_mask());
+        assert_eq!(geotiff.overviews()[0].mask, Some(3));
+
+        assert_eq!(
+            geotiff.read_mask_window(0, 1, 2, 2).unwrap(),
+            Some(vec![true, true, false, false])
+        );
+        assert!(geotiff.is_valid(2, 2));
+        assert!(!geotiff.is_valid(1, 2));
+        // pixels whose mask can't be read are invalid
+        assert!(!geotiff.is_valid(4, 0));
+        assert_eq!(geotiff.get_pixel(1, 2, 0), None);
+        assert_eq!(geotiff.get_pixel(2, 2, 0), Some(RasterValue::U8(10)));
+        let physical = geotiff.read_window_physical(1, 2, 2, 1).unwrap();
+        assert!(physical[0].is_nan());
+        assert_eq!(physical[1], 10.0);
+
+        // the mask of the overview
+        assert_eq!(
+            geotiff.read_mask_window_scaled(0, 0, 4, 4, 2, 2).unwrap(),
+            Some(vec![true, false, true, true])
+        );
+        // the mask of the full-resolution image
+        assert_eq!(
+            geotiff.read_mask_window_scaled(0, 1, 2, 2, 2, 2).unwrap(),
+            Some(vec![true, true, false, false])
+        );
+    }
+
+    #[test]
+    fn overviews_without_masks_use_the_mask_of_the_image() {
+        let [image, overview] = pyramid();
+        let geotiff = open_all(&[image, mask(4, 4, 4, vec![0xf0, 0xe0, 0xf0, 0xf0]), overview]);
+        assert_eq!(geotiff.overviews()[0].mask, None);
+        assert_eq!(
+            geotiff.read_mask_window_scaled(0, 0, 4, 4, 2, 2).unwrap(),
+            Some(vec![true, false, true, true])
+        );
+    }
+
+    #[test]
+    fn images_without_masks() {
+        let geotiff = open_all(&pyramid());
+        assert!(!geotiff.has_mask());
+        assert_eq!(geotiff.read_mask_window(0, 0, 4, 4).unwrap(), None);
+        assert_eq!(
+            geotiff.read_mask_window_scaled(0, 0, 4, 4, 2, 2).unwrap(),
+            None
+        );
+        assert!(geotiff.is_valid(3, 3));
+    }
+
+    #[test]
+    fn integer_nodata() {
+        let samples = [-32768i16, 5, -1, 7];
+        let ifd = TestIfd::stripped(2, 2, 2)
+            .short(TAG_BITS_PER_SAMPLE, &[16])
+            .short(TAG_SAMPLE_FORMAT, &[2])
+            .ascii(TAG_GDAL_NODATA, "-32768")
+            .strips(vec![samples.iter().flat_map(|v| v.to_le_bytes()).collect()]);
+        let geotiff = open(ifd);
+        assert_eq!(geotiff.nodata(), Some(RasterValue::I16(-32768)));
+        assert_eq!(geotiff.get_pixel(0, 0, 0), None);
+        assert_eq!(geotiff.get_pixel(0, 1, 0), Some(RasterValue::I16(-1)));
+        assert_eq!(geotiff.get_pixel_as::<i16>(1, 0, 0), Some(5));
+        assert_eq!(geotiff.get_pixel_as::<i16>(0, 0, 0), None);
+        // the samples are of another type
+        assert_eq!(geotiff.get_pixel_as::<u16>(1, 0, 0), None);
+        // the whole image being decoded doesn't change the result
+        geotiff.data();
+        assert_eq!(geotiff.get_pixel(0, 0, 0), None);
+        assert_eq!(geotiff.get_pixel_as::<i16>(1, 1, 0), Some(7));
+    }
+
+    #[test]
+    fn float_nodata() {
+        let pixels = |nodata: &str| {
+            let samples = [f32::NAN, -9999.0, 1.5, 0.0];
+            let ifd = TestIfd::stripped(4, 1, 1)
+                .short(TAG_BITS_PER_SAMPLE, &[32])
+                .short(TAG_SAMPLE_FORMAT, &[3])
+                .ascii(TAG_GDAL_NODATA, nodata)
+                .strips(vec![samples.iter().flat_map(|v| v.to_le_bytes()).collect()]);
+            let geotiff = open(ifd);
+            (0..4)
+                .map(|x| geotiff.get_pixel_as::<f32>(x, 0, 0))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            pixels("nan"),
+            vec![None, Some(-9999.0), Some(1.5), Some(0.0)]
+        );
+        let pixels = pixels("-9999");
+        // NaN samples are only nodata if the nodata value is NaN
+        assert!(pixels[0].is_some_and(f32::is_nan));
+        assert_eq!(pixels[1..], [None, Some(1.5), Some(0.0)]);
+    }
+
+    #[test]
+    fn nodata_out_of_range() {
+        let ifd = TestIfd::stripped(2, 1, 1)
+            .ascii(TAG_GDAL_NODATA, "-9999")
+            .strips(vec![vec![0, 255]]);
+        let geotiff = open(ifd);
+        assert_eq!(geotiff.nodata(), None);
+        assert_eq!(geotiff.get_pixel_as::<u8>(0, 0, 0), Some(0));
+        assert_eq!(geotiff.get_pixel_as::<u8>(1, 0, 0), Some(255));
 
-    pub fn get_pixel(&self, lon: usize, lat: usize) -> i32 {
-        let ifd = &self.tiff.ifds[0];
-        let width = ifd.image_width().unwrap() as usize;
-        let length = ifd.image_length().unwrap() as usize;
-        ifd.data[(length - 1 - lat) * width + lon]
+        let geotiff = open(TestIfd::stripped(1, 1, 1).strips(vec![vec![0]]));
+        assert_eq!(geotiff.nodata(), None);
     }
 }
@@ -0,0 +1,156 @@
+/// The method used to compute a value at an arbitrary point between pixel centers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// The value of the pixel containing the point.
+    Nearest,
+    /// Linear interpolation between the 2x2 nearest pixel centers.
+    Bilinear,
+    /// Cubic convolution (Catmull-Rom) over the 4x4 nearest pixel centers.
+    Bicubic,
+}
+
+impl Interpolation {
+    /// Interpolates the value at the raster coordinates `(col, row)`, fetching pixel values with
+    /// `get_pixel`. Returns `None` if the point lies outside of the raster, or if any of the
+    /// contributing pixels is `None`. Pixels beyond the edges are replaced by the nearest edge
+    /// pixels.
+    pub(crate) fn interpolate<F>(
+        &self,
+        col: f64,
+        row: f64,
+        width: usize,
+        height: usize,
+        get_pixel: F,
+    ) -> Option<f64>
+    where
+        F: Fn(usize, usize) -> Option<f64>,
+    {
+        if !(col >= 0.0 && row >= 0.0 && col < width as f64 && row < height as f64) {
+            return None;
+        }
+        let clamp = |index: isize, len: usize| index.clamp(0, len as isize - 1) as usize;
+
+        match self {
+            Interpolation::Nearest => get_pixel(col as usize, row as usize),
+            Interpolation::Bilinear => {
+                let (u, v) = (col - 0.5, row - 0.5);
+                let (i, j) = (u.floor(), v.floor());
+                let (fx, fy) = (u - i, v - j);
+                let (i, j) = (i as isize, j as isize);
+                let mut result = 0.0;
+                for (dj, wy) in [(0, 1.0 - fy), (1, fy)] {
+                    for (di, wx) in [(0, 1.0 - fx), (1, fx)] {
+                        let value = get_pixel(clamp(i + di, width), clamp(j + dj, height))?;
+                        result += wx * wy * value;
+                    }
+                }
+                Some(result)
+            }
+            Interpolation::Bicubic => {
+                let (u, v) = (col - 0.5, row - 0.5);
+                let (i, j) = (u.floor(), v.floor());
+                let (wxs, wys) = (cubic_weights(u - i), cubic_weights(v - j));
+                let (i, j) = (i as isize, j as isize);
+                let mut result = 0.0;
+                for (dj, wy) in (-1..=2).zip(wys) {
+                    for (di, wx) in (-1..=2).zip(wxs) {
+                        let value = get_pixel(clamp(i + di, width), clamp(j + dj, height))?;
+                        result += wx * wy * value;
+                    }
+                }
+                Some(result)
+            }
+        }
+    }
+}
+
+/// Catmull-Rom weights of the pixels at offsets -1, 0, 1 and 2 from a point `t` past offset 0.
+fn cubic_weights(t: f64) -> [f64; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 4 by 4 raster whose values grow linearly, by 1 per column and 10 per row.
+    fn linear(i: usize, j: usize) -> Option<f64> {
+        Some(i as f64 + 10.0 * j as f64)
+    }
+
+    fn interpolate(interpolation: Interpolation, col: f64, row: f64) -> Option<f64> {
+        interpolation.interpolate(col, row, 4, 4, linear)
+    }
+
+    #[test]
+    fn nearest() {
+        assert_eq!(interpolate(Interpolation::Nearest, 1.7, 2.2), Some(21.0));
+        assert_eq!(interpolate(Interpolation::Nearest, 0.0, 3.99), Some(30.0));
+    }
+
+    #[test]
+    fn bilinear() {
+        assert_eq!(interpolate(Interpolation::Bilinear, 1.5, 1.5), Some(11.0));
+        assert_eq!(interpolate(Interpolation::Bilinear, 1.0, 1.25), Some(8.0));
+        // beyond the outermost pixel centers the edge pixels are repeated
+        assert_eq!(interpolate(Interpolation::Bilinear, 0.25, 1.5), Some(10.0));
+        assert_eq!(interpolate(Interpolation::Bilinear, 3.75, 3.75), Some(33.0));
+    }
+
+    #[test]
+    fn bicubic() {
+        // cubic convolution reproduces linear functions away from the edges
+        assert_eq!(interpolate(Interpolation::Bicubic, 1.5, 1.5), Some(11.0));
+        assert_eq!(interpolate(Interpolation::Bicubic, 1.75, 2.0), Some(16.25));
+        // a single peak overshoots to negative values next to it
+        let peak = |i, j| Some(if (i, j) == (1, 1) { 1.0 } else { 0.0 });
+        let value = Interpolation::Bicubic.interpolate(2.5, 1.5, 4, 4, peak);
+        assert_eq!(value, Some(0.0));
+        let value = Interpolation::Bicubic.interpolate(2.0, 1.5, 4, 4, peak);
+        assert_eq!(value, Some(0.5625));
+        let value = Interpolation::Bicubic.interpolate(3.0, 1.5, 4, 4, peak);
+        assert_eq!(value, Some(-0.0625));
+    }
+
+    #[test]
+    fn points_outside_of_the_raster() {
+        for interpolation in [
+            Interpolation::Nearest,
+            Interpolation::Bilinear,
+            Interpolation::Bicubic,
+        ] {
+            assert_eq!(interpolate(interpolation, -0.1, 1.0), None);
+            assert_eq!(interpolate(interpolation, 1.0, 4.0), None);
+            assert_eq!(interpolate(interpolation, f64::NAN, 1.0), None);
+        }
+    }
+
+    #[test]
+    fn nodata_pixels() {
+        let get_pixel = |i, j| if (i, j) == (1, 1) { None } else { linear(i, j) };
+        assert_eq!(
+            Interpolation::Nearest.interpolate(2.5, 2.5, 4, 4, get_pixel),
+            Some(22.0)
+        );
+        assert_eq!(
+            Interpolation::Bilinear.interpolate(1.0, 1.0, 4, 4, get_pixel),
+            None
+        );
+        assert_eq!(
+            Interpolation::Bilinear.interpolate(2.5, 2.5, 4, 4, get_pixel),
+            Some(22.0)
+        );
+        // the 4 by 4 neighbourhood of bicubic interpolation reaches further
+        assert_eq!(
+            Interpolation::Bicubic.interpolate(2.5, 2.5, 4, 4, get_pixel),
+            None
+        );
+    }
+}
@@ -1,5 +1,21 @@
+mod gdal_metadata;
+mod geo_keys;
+mod geo_transform;
 mod geotiff;
+mod interpolation;
+mod mask;
+mod overview;
 mod parser;
 
+pub use gdal_metadata::{GdalMetadata, MetadataItems};
+pub use geo_keys::{GeoKey, GeoKeyDirectory, GeoKeyValue};
+pub use geo_transform::GeoTransform;
 pub use geotiff::GeoTiff;
-pub use parser::{TiffFile, TiffParserError};
+pub use interpolation::Interpolation;
+pub use overview::Overview;
+#[cfg(feature = "http")]
+pub use parser::HttpSource;
+pub use parser::{
+    ByteSource, Decompressor, DecompressorRegistry, Endianness, Ifd, RasterBuffer, RasterValue,
+    Sample, TiffFile, TiffOptions, TiffParserError, TiffReader, Value,
+};
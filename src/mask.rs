@@ -0,0 +1,53 @@
+use super::Ifd;
+
+/// Finds the transparency mask of the image in IFD `image` among the given IFDs, which are
+/// paired with their indices in the `TiffReader` of the file. A mask has the same size as its
+/// image, and GDAL writes it right after it, so the mask closest to the image wins if there are
+/// several candidates.
+pub(crate) fn find_mask(ifds: &[(usize, &Ifd)], image: usize) -> Option<usize> {
+    let (_, target) = ifds.iter().find(|(index, _)| *index == image)?;
+    ifds.iter()
+        .filter(|(_, ifd)| {
+            ifd.is_mask()
+                && ifd.is_reduced_resolution() == target.is_reduced_resolution()
+                && ifd.image_width() == target.image_width()
+                && ifd.image_length() == target.image_length()
+        })
+        .min_by_key(|(index, _)| (index.abs_diff(image), *index < image))
+        .map(|(index, _)| *index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{
+        tags::TAG_NEW_SUBFILE_TYPE,
+        test_tiff::{write_tiff, TestIfd},
+        TiffFile,
+    };
+
+    #[test]
+    fn closest_mask_of_the_same_size() {
+        let image = |width, subfile_type| {
+            TestIfd::stripped(width, 2, 2).long(TAG_NEW_SUBFILE_TYPE, &[subfile_type])
+        };
+        let tiff = TiffFile::from_bytes(&write_tiff(&[
+            image(4, 0),
+            // a mask of another size, and one of an overview
+            image(2, 4),
+            image(4, 5),
+            image(4, 4),
+            image(4, 0),
+            image(4, 4),
+            image(4, 0),
+        ]))
+        .unwrap();
+        let ifds = tiff.ifds().iter().enumerate().collect::<Vec<_>>();
+        assert_eq!(find_mask(&ifds, 0), Some(3));
+        // GDAL writes masks after their images
+        assert_eq!(find_mask(&ifds, 4), Some(5));
+        assert_eq!(find_mask(&ifds, 6), Some(5));
+        assert_eq!(find_mask(&ifds[..3], 0), None);
+        assert_eq!(find_mask(&ifds, 7), None);
+    }
+}
@@ -0,0 +1,135 @@
+use super::{mask::find_mask, Ifd};
+
+/// A reduced-resolution version of an image, as found in the overview pyramid of a GeoTIFF.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Overview {
+    /// The index of the IFD of the overview in the `TiffReader` of the file.
+    pub ifd: usize,
+    pub width: usize,
+    pub height: usize,
+    /// The width of a pixel of the overview, in pixels of the full-resolution image.
+    pub scale_x: f64,
+    /// The height of a pixel of the overview, in pixels of the full-resolution image.
+    pub scale_y: f64,
+    /// The index of the IFD of the transparency mask of the overview, if it has one.
+    pub mask: Option<usize>,
+}
+
+impl Overview {
+    /// Finds the overviews of the full-resolution image in IFD `image` among the IFDs related
+    /// to it, given with their indices in the `TiffReader` of the file, ordered from the most to
+    /// the least detailed.
+    pub(crate) fn find(ifds: &[(usize, &Ifd)], image: usize) -> Vec<Overview> {
+        let Some((_, target)) = ifds.iter().find(|(index, _)| *index == image) else {
+            return vec![];
+        };
+        let width = target.image_width().unwrap_or(0) as f64;
+        let height = target.image_length().unwrap_or(0) as f64;
+        let mut overviews = ifds
+            .iter()
+            .filter(|(_, ifd)| ifd.is_reduced_resolution() && !ifd.is_mask())
+            .filter_map(|&(index, ifd)| {
+                let overview_width = ifd.image_width()? as usize;
+                let overview_height = ifd.image_length()? as usize;
+                if overview_width == 0 || overview_height == 0 {
+                    return None;
+                }
+                Some(Overview {
+                    ifd: index,
+                    width: overview_width,
+                    height: overview_height,
+                    scale_x: width / overview_width as f64,
+                    scale_y: height / overview_height as f64,
+                    mask: find_mask(ifds, index),
+                })
+            })
+            .collect::<Vec<_>>();
+        overviews.sort_by(|a, b| a.scale_x.total_cmp(&b.scale_x));
+        overviews
+    }
+
+    /// Picks the least detailed of the given overviews which still has at least the resolution
+    /// of `scale` full-resolution pixels per output pixel, or `None` if none of them does and
+    /// the full-resolution image should be used.
+    pub(crate) fn best(overviews: &[Overview], scale: f64) -> Option<&Overview> {
+        // allow for the rounding of the sizes of the overviews
+        const TOLERANCE: f64 = 1.01;
+        overviews
+            .iter()
+            .rev()
+            .find(|overview| overview.scale_x.min(overview.scale_y) <= scale * TOLERANCE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{
+        tags::TAG_NEW_SUBFILE_TYPE,
+        test_tiff::{write_tiff, TestIfd},
+        TiffFile,
+    };
+
+    fn overview(ifd: usize, scale: f64) -> Overview {
+        Overview {
+            ifd,
+            width: 1,
+            height: 1,
+            scale_x: scale,
+            scale_y: scale,
+            mask: None,
+        }
+    }
+
+    #[test]
+    fn overviews_are_ordered_by_scale() {
+        let level = |width, length, subfile_type| {
+            TestIfd::stripped(width, length, length).long(TAG_NEW_SUBFILE_TYPE, &[subfile_type])
+        };
+        let tiff = TiffFile::from_bytes(&write_tiff(&[
+            TestIfd::stripped(8, 6, 6),
+            // the mask of the image
+            level(8, 6, 4),
+            level(2, 2, 1),
+            level(4, 3, 1),
+            // the mask of the second overview
+            level(4, 3, 5),
+        ]))
+        .unwrap();
+        let ifds = tiff.ifds().iter().enumerate().collect::<Vec<_>>();
+        assert_eq!(
+            Overview::find(&ifds, 0),
+            vec![
+                Overview {
+                    ifd: 3,
+                    width: 4,
+                    height: 3,
+                    scale_x: 2.0,
+                    scale_y: 2.0,
+                    mask: Some(4),
+                },
+                Overview {
+                    ifd: 2,
+                    width: 2,
+                    height: 2,
+                    scale_x: 4.0,
+                    scale_y: 3.0,
+                    mask: None,
+                },
+            ]
+        );
+        assert_eq!(Overview::find(&ifds, 5), vec![]);
+    }
+
+    #[test]
+    fn best() {
+        let overviews = [overview(1, 2.0), overview(2, 4.0)];
+        assert_eq!(Overview::best(&overviews, 1.0), None);
+        // overviews whose size was rounded still qualify
+        assert_eq!(Overview::best(&overviews, 1.99), Some(&overviews[0]));
+        assert_eq!(Overview::best(&overviews, 3.0), Some(&overviews[0]));
+        assert_eq!(Overview::best(&overviews, 4.0), Some(&overviews[1]));
+        assert_eq!(Overview::best(&overviews, 100.0), Some(&overviews[1]));
+        assert_eq!(Overview::best(&[], 100.0), None);
+    }
+}
@@ -1,19 +1,140 @@
+use std::{collections::HashMap, io::Read, sync::Arc};
+
+use flate2::read::ZlibDecoder;
 use weezl::{decode::Decoder, BitOrder, LzwStatus};
 
-use super::TiffParserError;
+use super::{endianness::Endianness, ifd::Ifd, TiffParserError};
+
+mod ccitt;
+mod jpeg;
+#[cfg(feature = "jxl")]
+mod jxl;
+#[cfg(feature = "lerc")]
+mod lerc;
+#[cfg(feature = "webp")]
+mod webp;
 
+/// Decodes the strips or tiles of images using one particular compression scheme.
 pub trait Decompressor {
+    /// Decompresses a single strip or tile, returning `size` bytes. Returning fewer bytes
+    /// marks the data as truncated.
     fn decompress(&mut self, bytes: &[u8], size: usize) -> Result<Vec<u8>, TiffParserError>;
 }
 
-const COMPRESSION_NONE: u16 = 1;
+type DecompressorFactory =
+    dyn Fn(&Ifd, Endianness) -> Result<Box<dyn Decompressor>, TiffParserError> + Send + Sync;
+
+pub(super) const COMPRESSION_NONE: u16 = 1;
+const COMPRESSION_CCITT_RLE: u16 = 2;
+const COMPRESSION_CCITT_T4: u16 = 3;
+const COMPRESSION_CCITT_T6: u16 = 4;
 const COMPRESSION_LZW: u16 = 5;
+pub(super) const COMPRESSION_JPEG: u16 = 7;
+const COMPRESSION_DEFLATE: u16 = 8;
+const COMPRESSION_PACKBITS: u16 = 32773;
+const COMPRESSION_ADOBE_DEFLATE: u16 = 32946;
+#[cfg(feature = "lerc")]
+const COMPRESSION_LERC: u16 = 34887;
+#[cfg(feature = "zstd")]
+const COMPRESSION_ZSTD: u16 = 50000;
+#[cfg(feature = "webp")]
+const COMPRESSION_WEBP: u16 = 50001;
+#[cfg(feature = "jxl")]
+const COMPRESSION_JXL: u16 = 50002;
+
+/// Maps compression codes to factories creating the decompressors for them.
+///
+/// A new registry contains all the decompressors built into this crate, additional ones can be
+/// added with `register`.
+#[derive(Clone)]
+pub struct DecompressorRegistry {
+    factories: HashMap<u16, Arc<DecompressorFactory>>,
+}
+
+impl DecompressorRegistry {
+    pub fn new() -> Self {
+        let mut registry = Self {
+            factories: HashMap::new(),
+        };
+        registry.register(COMPRESSION_NONE, |_, _| Ok(Box::new(DummyDecompressor)));
+        for compression in [
+            COMPRESSION_CCITT_RLE,
+            COMPRESSION_CCITT_T4,
+            COMPRESSION_CCITT_T6,
+        ] {
+            registry.register(compression, move |ifd, _| {
+                Ok(Box::new(ccitt::CcittDecompressor::new(compression, ifd)?))
+            });
+        }
+        registry.register(COMPRESSION_LZW, |_, _| {
+            Ok(Box::new(Decoder::with_tiff_size_switch(BitOrder::Msb, 8)))
+        });
+        registry.register(COMPRESSION_JPEG, |ifd, _| {
+            Ok(Box::new(jpeg::JpegDecompressor::new(ifd)?))
+        });
+        registry.register(COMPRESSION_DEFLATE, |_, _| {
+            Ok(Box::new(DeflateDecompressor))
+        });
+        registry.register(COMPRESSION_ADOBE_DEFLATE, |_, _| {
+            Ok(Box::new(DeflateDecompressor))
+        });
+        registry.register(COMPRESSION_PACKBITS, |_, _| {
+            Ok(Box::new(PackBitsDecompressor))
+        });
+        #[cfg(feature = "lerc")]
+        registry.register(COMPRESSION_LERC, |ifd, endianness| {
+            Ok(Box::new(lerc::LercDecompressor::new(endianness, ifd)?))
+        });
+        #[cfg(feature = "zstd")]
+        registry.register(COMPRESSION_ZSTD, |_, _| Ok(Box::new(ZstdDecompressor)));
+        #[cfg(feature = "webp")]
+        registry.register(COMPRESSION_WEBP, |ifd, _| {
+            Ok(Box::new(webp::WebPDecompressor::new(ifd)?))
+        });
+        #[cfg(feature = "jxl")]
+        registry.register(COMPRESSION_JXL, |ifd, endianness| {
+            Ok(Box::new(jxl::JxlDecompressor::new(endianness, ifd)?))
+        });
+        registry
+    }
 
-pub fn create_decompressor(compression: u16) -> Result<Box<dyn Decompressor>, TiffParserError> {
-    match compression {
-        COMPRESSION_NONE => Ok(Box::new(DummyDecompressor)),
-        COMPRESSION_LZW => Ok(Box::new(Decoder::with_tiff_size_switch(BitOrder::Msb, 8))),
-        compression => Err(TiffParserError::UnknownCompression(compression)),
+    /// Registers a factory for the decompressor of the given compression code, replacing the
+    /// existing one if there is one.
+    ///
+    /// The factory is called for every strip or tile with the IFD it belongs to and the
+    /// endianness of the file, so that the decompressor can read any parameters stored in the
+    /// IFD and return multi-byte samples in the byte order of the file.
+    pub fn register<F>(&mut self, compression: u16, factory: F)
+    where
+        F: Fn(&Ifd, Endianness) -> Result<Box<dyn Decompressor>, TiffParserError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.factories.insert(compression, Arc::new(factory));
+    }
+
+    /// Returns whether a decompressor is registered for the given compression code.
+    pub fn contains(&self, compression: u16) -> bool {
+        self.factories.contains_key(&compression)
+    }
+
+    pub(super) fn create(
+        &self,
+        compression: u16,
+        endianness: Endianness,
+        ifd: &Ifd,
+    ) -> Result<Box<dyn Decompressor>, TiffParserError> {
+        match self.factories.get(&compression) {
+            Some(factory) => factory(ifd, endianness),
+            None => Err(TiffParserError::UnknownCompression(compression)),
+        }
+    }
+}
+
+impl Default for DecompressorRegistry {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -21,7 +142,74 @@ struct DummyDecompressor;
 
 impl Decompressor for DummyDecompressor {
     fn decompress(&mut self, bytes: &[u8], size: usize) -> Result<Vec<u8>, TiffParserError> {
-        Ok(bytes[..size].to_vec())
+        // a truncated chunk is returned as it is and rejected by the caller
+        Ok(bytes[..size.min(bytes.len())].to_vec())
+    }
+}
+
+struct DeflateDecompressor;
+
+impl Decompressor for DeflateDecompressor {
+    fn decompress(&mut self, bytes: &[u8], size: usize) -> Result<Vec<u8>, TiffParserError> {
+        let mut result = Vec::with_capacity(size);
+        ZlibDecoder::new(bytes)
+            .take(size as u64)
+            .read_to_end(&mut result)?;
+        Ok(result)
+    }
+}
+
+#[cfg(feature = "zstd")]
+struct ZstdDecompressor;
+
+#[cfg(feature = "zstd")]
+impl ZstdDecompressor {
+    /// Decompresses at most `limit` bytes of a Zstandard stream.
+    fn decompress_limited(bytes: &[u8], limit: usize) -> Result<Vec<u8>, TiffParserError> {
+        let mut result = vec![];
+        ruzstd::decoding::StreamingDecoder::new(bytes)?
+            .take(limit as u64)
+            .read_to_end(&mut result)?;
+        Ok(result)
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Decompressor for ZstdDecompressor {
+    fn decompress(&mut self, bytes: &[u8], size: usize) -> Result<Vec<u8>, TiffParserError> {
+        Self::decompress_limited(bytes, size)
+    }
+}
+
+struct PackBitsDecompressor;
+
+impl Decompressor for PackBitsDecompressor {
+    fn decompress(&mut self, bytes: &[u8], size: usize) -> Result<Vec<u8>, TiffParserError> {
+        let mut result = Vec::with_capacity(size);
+        let mut pos = 0;
+        while pos < bytes.len() && result.len() < size {
+            let header = bytes[pos] as i8;
+            pos += 1;
+            match header {
+                // a literal run of header + 1 bytes
+                0..=127 => {
+                    let end = (pos + header as usize + 1).min(bytes.len());
+                    result.extend_from_slice(&bytes[pos..end]);
+                    pos = end;
+                }
+                // a no-op
+                -128 => {}
+                // the next byte repeated 1 - header times
+                _ => {
+                    if let Some(byte) = bytes.get(pos) {
+                        result.extend(std::iter::repeat_n(*byte, (1 - header as isize) as usize));
+                    }
+                    pos += 1;
+                }
+            }
+        }
+        result.truncate(size);
+        Ok(result)
     }
 }
 
@@ -37,17 +225,11 @@ impl Decompressor for Decoder {
             consumed_out += decode_result.consumed_out;
             match decode_result.status {
                 Ok(LzwStatus::Ok) => {}
-                Ok(LzwStatus::NoProgress) => {
-                    return Ok(result);
-                }
-                Ok(LzwStatus::Done) => {
+                Ok(LzwStatus::NoProgress) | Ok(LzwStatus::Done) => {
+                    result.truncate(consumed_out);
                     return Ok(result);
                 }
                 Err(err) => {
-                    eprintln!(
-                        "error {}; size = {}, consumed_out = {}",
-                        err, size, consumed_out
-                    );
                     if consumed_out >= size {
                         return Ok(result);
                     } else {
@@ -61,3 +243,111 @@ impl Decompressor for Decoder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{
+        raster_buffer::RasterBuffer,
+        tags::TAG_COMPRESSION,
+        test_tiff::{write_tiff, TestIfd},
+        TiffFile, TiffOptions,
+    };
+
+    /// A decompressor inverting every byte of the data.
+    struct InvertingDecompressor;
+
+    impl Decompressor for InvertingDecompressor {
+        fn decompress(&mut self, bytes: &[u8], size: usize) -> Result<Vec<u8>, TiffParserError> {
+            Ok(bytes.iter().take(size).map(|byte| !byte).collect())
+        }
+    }
+
+    fn decode(compression: u16, options: &TiffOptions) -> Result<RasterBuffer, TiffParserError> {
+        let ifd = TestIfd::stripped(2, 1, 1)
+            .short(TAG_COMPRESSION, &[compression])
+            .strips(vec![vec![0, 10]]);
+        let tiff = TiffFile::from_bytes_with_options(&write_tiff(&[ifd]), options)?;
+        Ok(tiff.ifds()[0].data().unwrap().clone())
+    }
+
+    #[test]
+    fn custom_decompressor() {
+        let mut options = TiffOptions::default();
+        assert!(!options.decompressors.contains(65000));
+        assert!(matches!(
+            decode(65000, &options),
+            Err(TiffParserError::UnknownCompression(65000))
+        ));
+        options
+            .decompressors
+            .register(65000, |_, _| Ok(Box::new(InvertingDecompressor)));
+        assert!(options.decompressors.contains(65000));
+        assert_eq!(
+            decode(65000, &options).unwrap(),
+            RasterBuffer::U8(vec![255, 245])
+        );
+    }
+
+    #[test]
+    fn built_in_decompressors_can_be_replaced() {
+        let mut options = TiffOptions::default();
+        assert_eq!(
+            decode(COMPRESSION_NONE, &options).unwrap(),
+            RasterBuffer::U8(vec![0, 10])
+        );
+        options
+            .decompressors
+            .register(COMPRESSION_NONE, |_, _| Ok(Box::new(InvertingDecompressor)));
+        assert_eq!(
+            decode(COMPRESSION_NONE, &options).unwrap(),
+            RasterBuffer::U8(vec![255, 245])
+        );
+        // the default registry is unaffected
+        assert_eq!(
+            decode(COMPRESSION_NONE, &TiffOptions::default()).unwrap(),
+            RasterBuffer::U8(vec![0, 10])
+        );
+    }
+
+    #[test]
+    fn packbits() {
+        // the example of the TIFF 6.0 specification
+        let packed = [
+            0xfe, 0xaa, 0x02, 0x80, 0x00, 0x2a, 0xfd, 0xaa, 0x03, 0x80, 0x00, 0x2a, 0x22, 0xf7,
+            0xaa,
+        ];
+        let mut unpacked = vec![0xaa, 0xaa, 0xaa, 0x80, 0x00, 0x2a, 0xaa, 0xaa, 0xaa, 0xaa];
+        unpacked.extend([0x80, 0x00, 0x2a, 0x22]);
+        unpacked.extend([0xaa; 10]);
+        assert_eq!(
+            PackBitsDecompressor.decompress(&packed, 24).unwrap(),
+            unpacked
+        );
+        // the output stops at the expected size, and a no-op header is skipped
+        assert_eq!(
+            PackBitsDecompressor
+                .decompress(&[0x80, 0xfd, 0x07], 2)
+                .unwrap(),
+            [7, 7]
+        );
+        // a stream ending early is returned as it is
+        assert_eq!(
+            PackBitsDecompressor.decompress(&[0x02, 1, 2], 8).unwrap(),
+            [1, 2]
+        );
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_output_is_bounded() {
+        let compressed = ruzstd::encoding::compress_to_vec(
+            &[0u8; 1 << 20][..],
+            ruzstd::encoding::CompressionLevel::Fastest,
+        );
+        assert_eq!(
+            ZstdDecompressor.decompress(&compressed, 16).unwrap(),
+            [0; 16]
+        );
+    }
+}
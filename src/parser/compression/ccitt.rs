@@ -0,0 +1,595 @@
+use std::sync::OnceLock;
+
+use super::{
+    super::{ifd::Ifd, tags::TAG_T4_OPTIONS, TiffParserError},
+    Decompressor, COMPRESSION_CCITT_RLE, COMPRESSION_CCITT_T6,
+};
+
+// T4Options flags
+const T4_2D_CODING: u32 = 1;
+const T4_UNCOMPRESSED: u32 = 2;
+
+/// The longest code in any of the tables below is 13 bits long.
+const MAX_CODE_LENGTH: u8 = 13;
+
+/// Terminating (0-63) and makeup (64-2560) codes for white runs, from ITU-T T.4 tables 2 and 3.
+const WHITE_CODES: &[(u16, &str)] = &[
+    (0, "00110101"),
+    (1, "000111"),
+    (2, "0111"),
+    (3, "1000"),
+    (4, "1011"),
+    (5, "1100"),
+    (6, "1110"),
+    (7, "1111"),
+    (8, "10011"),
+    (9, "10100"),
+    (10, "00111"),
+    (11, "01000"),
+    (12, "001000"),
+    (13, "000011"),
+    (14, "110100"),
+    (15, "110101"),
+    (16, "101010"),
+    (17, "101011"),
+    (18, "0100111"),
+    (19, "0001100"),
+    (20, "0001000"),
+    (21, "0010111"),
+    (22, "0000011"),
+    (23, "0000100"),
+    (24, "0101000"),
+    (25, "0101011"),
+    (26, "0010011"),
+    (27, "0100100"),
+    (28, "0011000"),
+    (29, "00000010"),
+    (30, "00000011"),
+    (31, "00011010"),
+    (32, "00011011"),
+    (33, "00010010"),
+    (34, "00010011"),
+    (35, "00010100"),
+    (36, "00010101"),
+    (37, "00010110"),
+    (38, "00010111"),
+    (39, "00101000"),
+    (40, "00101001"),
+    (41, "00101010"),
+    (42, "00101011"),
+    (43, "00101100"),
+    (44, "00101101"),
+    (45, "00000100"),
+    (46, "00000101"),
+    (47, "00001010"),
+    (48, "00001011"),
+    (49, "01010010"),
+    (50, "01010011"),
+    (51, "01010100"),
+    (52, "01010101"),
+    (53, "00100100"),
+    (54, "00100101"),
+    (55, "01011000"),
+    (56, "01011001"),
+    (57, "01011010"),
+    (58, "01011011"),
+    (59, "01001010"),
+    (60, "01001011"),
+    (61, "00110010"),
+    (62, "00110011"),
+    (63, "00110100"),
+    (64, "11011"),
+    (128, "10010"),
+    (192, "010111"),
+    (256, "0110111"),
+    (320, "00110110"),
+    (384, "00110111"),
+    (448, "01100100"),
+    (512, "01100101"),
+    (576, "01101000"),
+    (640, "01100111"),
+    (704, "011001100"),
+    (768, "011001101"),
+    (832, "011010010"),
+    (896, "011010011"),
+    (960, "011010100"),
+    (1024, "011010101"),
+    (1088, "011010110"),
+    (1152, "011010111"),
+    (1216, "011011000"),
+    (1280, "011011001"),
+    (1344, "011011010"),
+    (1408, "011011011"),
+    (1472, "010011000"),
+    (1536, "010011001"),
+    (1600, "010011010"),
+    (1664, "011000"),
+    (1728, "010011011"),
+    (1792, "00000001000"),
+    (1856, "00000001100"),
+    (1920, "00000001101"),
+    (1984, "000000010010"),
+    (2048, "000000010011"),
+    (2112, "000000010100"),
+    (2176, "000000010101"),
+    (2240, "000000010110"),
+    (2304, "000000010111"),
+    (2368, "000000011100"),
+    (2432, "000000011101"),
+    (2496, "000000011110"),
+    (2560, "000000011111"),
+];
+
+/// Terminating (0-63) and makeup (64-2560) codes for black runs, from ITU-T T.4 tables 2 and 3.
+const BLACK_CODES: &[(u16, &str)] = &[
+    (0, "0000110111"),
+    (1, "010"),
+    (2, "11"),
+    (3, "10"),
+    (4, "011"),
+    (5, "0011"),
+    (6, "0010"),
+    (7, "00011"),
+    (8, "000101"),
+    (9, "000100"),
+    (10, "0000100"),
+    (11, "0000101"),
+    (12, "0000111"),
+    (13, "00000100"),
+    (14, "00000111"),
+    (15, "000011000"),
+    (16, "0000010111"),
+    (17, "0000011000"),
+    (18, "0000001000"),
+    (19, "00001100111"),
+    (20, "00001101000"),
+    (21, "00001101100"),
+    (22, "00000110111"),
+    (23, "00000101000"),
+    (24, "00000010111"),
+    (25, "00000011000"),
+    (26, "000011001010"),
+    (27, "000011001011"),
+    (28, "000011001100"),
+    (29, "000011001101"),
+    (30, "000001101000"),
+    (31, "000001101001"),
+    (32, "000001101010"),
+    (33, "000001101011"),
+    (34, "000011010010"),
+    (35, "000011010011"),
+    (36, "000011010100"),
+    (37, "000011010101"),
+    (38, "000011010110"),
+    (39, "000011010111"),
+    (40, "000001101100"),
+    (41, "000001101101"),
+    (42, "000011011010"),
+    (43, "000011011011"),
+    (44, "000001010100"),
+    (45, "000001010101"),
+    (46, "000001010110"),
+    (47, "000001010111"),
+    (48, "000001100100"),
+    (49, "000001100101"),
+    (50, "000001010010"),
+    (51, "000001010011"),
+    (52, "000000100100"),
+    (53, "000000110111"),
+    (54, "000000111000"),
+    (55, "000000100111"),
+    (56, "000000101000"),
+    (57, "000001011000"),
+    (58, "000001011001"),
+    (59, "000000101011"),
+    (60, "000000101100"),
+    (61, "000001011010"),
+    (62, "000001100110"),
+    (63, "000001100111"),
+    (64, "0000001111"),
+    (128, "000011001000"),
+    (192, "000011001001"),
+    (256, "000001011011"),
+    (320, "000000110011"),
+    (384, "000000110100"),
+    (448, "000000110101"),
+    (512, "0000001101100"),
+    (576, "0000001101101"),
+    (640, "0000001001010"),
+    (704, "0000001001011"),
+    (768, "0000001001100"),
+    (832, "0000001001101"),
+    (896, "0000001110010"),
+    (960, "0000001110011"),
+    (1024, "0000001110100"),
+    (1088, "0000001110101"),
+    (1152, "0000001110110"),
+    (1216, "0000001110111"),
+    (1280, "0000001010010"),
+    (1344, "0000001010011"),
+    (1408, "0000001010100"),
+    (1472, "0000001010101"),
+    (1536, "0000001011010"),
+    (1600, "0000001011011"),
+    (1664, "0000001100100"),
+    (1728, "0000001100101"),
+    (1792, "00000001000"),
+    (1856, "00000001100"),
+    (1920, "00000001101"),
+    (1984, "000000010010"),
+    (2048, "000000010011"),
+    (2112, "000000010100"),
+    (2176, "000000010101"),
+    (2240, "000000010110"),
+    (2304, "000000010111"),
+    (2368, "000000011100"),
+    (2432, "000000011101"),
+    (2496, "000000011110"),
+    (2560, "000000011111"),
+];
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Pass,
+    Horizontal,
+    Vertical(isize),
+}
+
+/// Two-dimensional coding modes, from ITU-T T.4 table 4.
+const MODE_CODES: &[(Mode, &str)] = &[
+    (Mode::Pass, "0001"),
+    (Mode::Horizontal, "001"),
+    (Mode::Vertical(0), "1"),
+    (Mode::Vertical(1), "011"),
+    (Mode::Vertical(2), "000011"),
+    (Mode::Vertical(3), "0000011"),
+    (Mode::Vertical(-1), "010"),
+    (Mode::Vertical(-2), "000010"),
+    (Mode::Vertical(-3), "0000010"),
+];
+
+/// A table mapping every possible `MAX_CODE_LENGTH`-bit prefix of the input to the code it starts
+/// with and the length of that code.
+struct LookupTable<T>(Vec<Option<(T, u8)>>);
+
+impl<T: Copy> LookupTable<T> {
+    fn new(codes: &[(T, &str)]) -> Self {
+        let mut table = vec![None; 1 << MAX_CODE_LENGTH];
+        for (value, code) in codes {
+            let len = code.len() as u8;
+            let prefix = usize::from_str_radix(code, 2).unwrap() << (MAX_CODE_LENGTH - len);
+            for entry in &mut table[prefix..prefix + (1 << (MAX_CODE_LENGTH - len))] {
+                *entry = Some((*value, len));
+            }
+        }
+        Self(table)
+    }
+
+    fn read(&self, reader: &mut BitReader) -> Result<T, TiffParserError> {
+        let (value, len) = self.0[reader.peek(MAX_CODE_LENGTH) as usize]
+            .ok_or(TiffParserError::Ccitt("invalid code"))?;
+        reader.consume(len);
+        Ok(value)
+    }
+}
+
+struct LookupTables {
+    white: LookupTable<u16>,
+    black: LookupTable<u16>,
+    mode: LookupTable<Mode>,
+}
+
+fn lookup_tables() -> &'static LookupTables {
+    static TABLES: OnceLock<LookupTables> = OnceLock::new();
+    TABLES.get_or_init(|| LookupTables {
+        white: LookupTable::new(WHITE_CODES),
+        black: LookupTable::new(BLACK_CODES),
+        mode: LookupTable::new(MODE_CODES),
+    })
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    /// Returns the next `n` bits without consuming them, padded with zeros past the end of the
+    /// input.
+    fn peek(&self, n: u8) -> u32 {
+        (0..n as usize).fold(0, |acc, i| {
+            let pos = self.pos + i;
+            let bit = self
+                .bytes
+                .get(pos / 8)
+                .map_or(0, |byte| (byte >> (7 - pos % 8)) & 1);
+            (acc << 1) | bit as u32
+        })
+    }
+
+    fn consume(&mut self, n: u8) {
+        self.pos += n as usize;
+    }
+
+    fn read_bit(&mut self) -> u32 {
+        let bit = self.peek(1);
+        self.consume(1);
+        bit
+    }
+
+    fn align(&mut self) {
+        self.pos = self.pos.next_multiple_of(8);
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.pos >= self.bytes.len() * 8
+    }
+
+    /// Skips an EOL code, along with any fill bits before it, if there is one.
+    fn skip_eol(&mut self) -> bool {
+        // no code other than EOL starts with more than 7 zeros
+        if self.peek(11) != 0 {
+            return false;
+        }
+        while !self.is_at_end() && self.read_bit() == 0 {}
+        true
+    }
+}
+
+/// Decompressor for bilevel images compressed with CCITT modified Huffman run length encoding
+/// (compression 2), T.4 bilevel encoding (compression 3) or T.6 bilevel encoding
+/// (compression 4).
+///
+/// The result is packed with one bit per pixel, black runs being set to 1 and white runs to 0,
+/// which is why such images usually have a PhotometricInterpretation of WhiteIsZero.
+pub(super) struct CcittDecompressor {
+    compression: u16,
+    width: usize,
+    two_dimensional: bool,
+}
+
+impl CcittDecompressor {
+    pub(super) fn new(compression: u16, ifd: &Ifd) -> Result<Self, TiffParserError> {
+        let (width, _) = ifd.chunk_dimensions(ifd.is_tiled())?;
+        let two_dimensional = match compression {
+            COMPRESSION_CCITT_RLE => false,
+            COMPRESSION_CCITT_T6 => true,
+            _ => match ifd.get_value(TAG_T4_OPTIONS) {
+                Ok(value) => {
+                    let options = value.long()?;
+                    if options & T4_UNCOMPRESSED != 0 {
+                        return Err(TiffParserError::Ccitt("uncompressed mode is not supported"));
+                    }
+                    options & T4_2D_CODING != 0
+                }
+                Err(_) => false,
+            },
+        };
+        Ok(Self {
+            compression,
+            width,
+            two_dimensional,
+        })
+    }
+
+    /// Reads a run of the given color, made up of any number of makeup codes followed by a
+    /// terminating code.
+    fn read_run(reader: &mut BitReader, black: bool) -> Result<usize, TiffParserError> {
+        let tables = lookup_tables();
+        let table = if black { &tables.black } else { &tables.white };
+        let mut run = 0;
+        loop {
+            let length = table.read(reader)? as usize;
+            run += length;
+            if length < 64 {
+                return Ok(run);
+            }
+        }
+    }
+
+    /// Decodes a one-dimensionally coded row into the positions of its changing elements.
+    fn decode_1d(
+        &self,
+        reader: &mut BitReader,
+        changes: &mut Vec<usize>,
+    ) -> Result<(), TiffParserError> {
+        let mut a0 = 0;
+        let mut black = false;
+        while a0 < self.width {
+            a0 = (a0 + Self::read_run(reader, black)?).min(self.width);
+            changes.push(a0);
+            black = !black;
+        }
+        Ok(())
+    }
+
+    /// Decodes a two-dimensionally coded row into the positions of its changing elements, using
+    /// the changing elements of the previous row as reference.
+    fn decode_2d(
+        &self,
+        reader: &mut BitReader,
+        reference: &[usize],
+        changes: &mut Vec<usize>,
+    ) -> Result<(), TiffParserError> {
+        let tables = lookup_tables();
+        // a0 starts on an imaginary white pixel before the first one of the row
+        let mut a0: Option<usize> = None;
+        let mut black = false;
+        let mut index: usize = 0;
+        while a0.is_none_or(|a0| a0 < self.width) {
+            // b1 is the first changing element of the reference row to the right of a0 with
+            // the opposite color of a0, and b2 is the next one after it; the elements at even
+            // indices are changes to black, the ones at odd indices changes to white
+            index = index.saturating_sub(2);
+            while index < reference.len()
+                && (a0.is_some_and(|a0| reference[index] <= a0) || (index % 2 == 1) != black)
+            {
+                index += 1;
+            }
+            let b1 = reference.get(index).copied().unwrap_or(self.width);
+            let b2 = reference.get(index + 1).copied().unwrap_or(self.width);
+
+            match tables.mode.read(reader)? {
+                Mode::Pass => a0 = Some(b2),
+                Mode::Horizontal => {
+                    let start = a0.unwrap_or(0);
+                    let a1 = (start + Self::read_run(reader, black)?).min(self.width);
+                    let a2 = (a1 + Self::read_run(reader, !black)?).min(self.width);
+                    changes.push(a1);
+                    changes.push(a2);
+                    a0 = Some(a2);
+                }
+                Mode::Vertical(delta) => {
+                    let a1 = b1
+                        .checked_add_signed(delta)
+                        .ok_or(TiffParserError::Ccitt("changing element out of range"))?
+                        .min(self.width);
+                    changes.push(a1);
+                    a0 = Some(a1);
+                    black = !black;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Decompressor for CcittDecompressor {
+    fn decompress(&mut self, bytes: &[u8], size: usize) -> Result<Vec<u8>, TiffParserError> {
+        let row_size = self.width.div_ceil(8);
+        let rows = size / row_size.max(1);
+        let mut result = vec![];
+        let mut reader = BitReader { bytes, pos: 0 };
+        let mut reference = vec![];
+        let mut changes = vec![];
+        for _ in 0..rows {
+            // the rows missing from a stream ending early mark the data as truncated
+            if reader.is_at_end() {
+                break;
+            }
+            changes.clear();
+            match self.compression {
+                // every row is byte-aligned and there are no EOL codes
+                COMPRESSION_CCITT_RLE => {
+                    self.decode_1d(&mut reader, &mut changes)?;
+                    reader.align();
+                }
+                COMPRESSION_CCITT_T6 => self.decode_2d(&mut reader, &reference, &mut changes)?,
+                _ => {
+                    reader.skip_eol();
+                    // with 2D coding, every EOL is followed by a bit telling how the next row
+                    // is coded
+                    if self.two_dimensional && reader.read_bit() == 0 {
+                        self.decode_2d(&mut reader, &reference, &mut changes)?;
+                    } else {
+                        self.decode_1d(&mut reader, &mut changes)?;
+                    }
+                }
+            }
+
+            // changes alternate between the start of a black and the start of a white run
+            let start = result.len();
+            result.resize(start + row_size, 0);
+            let row = &mut result[start..];
+            for run in changes.chunks(2) {
+                let end = run.get(1).copied().unwrap_or(self.width);
+                for x in run[0]..end {
+                    row[x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+            // elements at the right edge of the row would only confuse the next one
+            changes.retain(|x| *x < self.width);
+            std::mem::swap(&mut reference, &mut changes);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{
+        compression::COMPRESSION_CCITT_T4,
+        tags::*,
+        test_tiff::{write_tiff, TestIfd},
+        RasterBuffer, TiffFile,
+    };
+
+    /// Rows of 8 pixels with runs of 3 white, 3 black and 2 white pixels, then 3 white, 4 black
+    /// and 1 white pixels, then 8 black pixels.
+    const BITMAP: [u8; 3] = [0b0001_1100, 0b0001_1110, 0b1111_1111];
+
+    fn decompress(compression: u16, two_dimensional: bool, bytes: &[u8]) -> Vec<u8> {
+        let mut decompressor = CcittDecompressor {
+            compression,
+            width: 8,
+            two_dimensional,
+        };
+        decompressor.decompress(bytes, BITMAP.len()).unwrap()
+    }
+
+    #[test]
+    fn modified_huffman() {
+        let bytes = [0x89, 0xc0, 0x86, 0x38, 0x35, 0x14];
+        assert_eq!(decompress(COMPRESSION_CCITT_RLE, false, &bytes), BITMAP);
+    }
+
+    #[test]
+    fn makeup_codes() {
+        // 70 white pixels, coded as 64 + 6, followed by 10 black pixels
+        let mut decompressor = CcittDecompressor {
+            compression: COMPRESSION_CCITT_RLE,
+            width: 80,
+            two_dimensional: false,
+        };
+        let mut row = vec![0; 8];
+        row.extend([0b0000_0011, 0b1111_1111]);
+        assert_eq!(decompressor.decompress(&[0xdf, 0x04], 10).unwrap(), row);
+    }
+
+    #[test]
+    fn group_3_one_dimensional() {
+        // every row is preceded by an EOL code
+        let bytes = [0x00, 0x18, 0x9c, 0x00, 0x61, 0x8e, 0x00, 0x26, 0xa2, 0x80];
+        assert_eq!(decompress(COMPRESSION_CCITT_T4, false, &bytes), BITMAP);
+    }
+
+    #[test]
+    fn group_3_two_dimensional() {
+        // the first row is coded in one dimension, the next ones relative to the previous row
+        let bytes = [0x00, 0x1c, 0x4e, 0x00, 0x2b, 0x80, 0x08, 0x13];
+        assert_eq!(decompress(COMPRESSION_CCITT_T4, true, &bytes), BITMAP);
+    }
+
+    #[test]
+    fn truncated_stream() {
+        // the first 2 rows of the modified Huffman example
+        let bytes = [0x89, 0xc0, 0x86, 0x38];
+        assert_eq!(
+            decompress(COMPRESSION_CCITT_RLE, false, &bytes),
+            BITMAP[..2]
+        );
+    }
+
+    #[test]
+    fn group_4() {
+        // the first row is coded relative to an imaginary white row
+        let bytes = [0x31, 0x6e, 0x09, 0x80];
+        assert_eq!(decompress(COMPRESSION_CCITT_T6, true, &bytes), BITMAP);
+    }
+
+    #[test]
+    fn stray_tile_width_of_a_stripped_image() {
+        let ifd = TestIfd::stripped(8, 1, 1)
+            .short(TAG_BITS_PER_SAMPLE, &[1])
+            .short(TAG_COMPRESSION, &[COMPRESSION_CCITT_T4])
+            .short(TAG_PHOTOMETRIC_INTERPRETATION, &[0])
+            .short(TAG_TILE_WIDTH, &[0])
+            .strips(vec![vec![0x00, 0x18, 0x9c]]);
+        let tiff = TiffFile::from_bytes(&write_tiff(&[ifd])).unwrap();
+        assert_eq!(
+            tiff.ifds()[0].data(),
+            Some(&RasterBuffer::U8(vec![1, 1, 1, 0, 0, 0, 1, 1]))
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use jpeg_decoder::{ColorTransform, Decoder, PixelFormat};
+
+use super::{
+    super::{
+        ifd::Ifd,
+        tags::{TAG_JPEG_TABLES, TAG_PHOTOMETRIC_INTERPRETATION},
+        TiffParserError,
+    },
+    Decompressor,
+};
+
+const PHOTOMETRIC_YCBCR: u16 = 6;
+
+pub(super) struct JpegDecompressor {
+    tables: Option<Vec<u8>>,
+    color_transform: ColorTransform,
+    /// The size of a whole strip or tile, which the decoded image mustn't exceed.
+    max_size: usize,
+}
+
+impl JpegDecompressor {
+    pub(super) fn new(ifd: &Ifd) -> Result<Self, TiffParserError> {
+        let tables = match ifd.get_value(TAG_JPEG_TABLES) {
+            Ok(value) => Some(value.undefined()?.to_vec()),
+            Err(_) => None,
+        };
+        // YCbCr data is converted to RGB, anything else is returned as it is stored
+        let color_transform = match ifd.get_value(TAG_PHOTOMETRIC_INTERPRETATION) {
+            Ok(value) if value.short()? == PHOTOMETRIC_YCBCR => ColorTransform::YCbCr,
+            _ => ColorTransform::None,
+        };
+        let (width, length) = ifd.chunk_dimensions(ifd.is_tiled())?;
+        // samples of more than 8 bits are decoded to 2 bytes
+        let max_size = width
+            .saturating_mul(length)
+            .saturating_mul(ifd.samples_per_pixel()? as usize)
+            .saturating_mul(2);
+        Ok(Self {
+            tables,
+            color_transform,
+            max_size,
+        })
+    }
+}
+
+impl Decompressor for JpegDecompressor {
+    fn decompress(&mut self, bytes: &[u8], size: usize) -> Result<Vec<u8>, TiffParserError> {
+        // JPEGTables is an abbreviated JPEG stream containing only the quantization and Huffman
+        // tables, so it gets spliced into the stream of each tile, between the SOI marker of the
+        // tile and the rest of its contents, dropping the EOI marker of the tables
+        let stream = match &self.tables {
+            Some(tables) if tables.len() >= 4 && bytes.len() >= 2 => {
+                let mut stream = Vec::with_capacity(tables.len() + bytes.len());
+                stream.extend_from_slice(&tables[..tables.len() - 2]);
+                stream.extend_from_slice(&bytes[2..]);
+                stream
+            }
+            _ => bytes.to_vec(),
+        };
+        let mut decoder = Decoder::new(stream.as_slice());
+        decoder.read_info()?;
+        // check the size declared by the stream before the decoder allocates its buffers
+        if let Some(info) = decoder.info() {
+            let decoded_size =
+                info.width as usize * info.height as usize * info.pixel_format.pixel_bytes();
+            if decoded_size > self.max_size {
+                return Err(jpeg_decoder::Error::Format(
+                    "the image is larger than a strip or tile".to_owned(),
+                )
+                .into());
+            }
+        }
+        // the decoder doesn't interleave the components of multi-component images without a
+        // color transform, so the transforms which only interleave them are used instead, CMYK
+        // inverting every sample
+        let (color_transform, invert) = match (self.color_transform, decoder.info()) {
+            (ColorTransform::None, Some(info)) => match info.pixel_format {
+                PixelFormat::RGB24 => (ColorTransform::RGB, false),
+                PixelFormat::CMYK32 => (ColorTransform::CMYK, true),
+                _ => (ColorTransform::None, false),
+            },
+            (color_transform, _) => (color_transform, false),
+        };
+        decoder.set_color_transform(color_transform);
+        let mut result = decoder.decode()?;
+        if invert {
+            result.iter_mut().for_each(|sample| *sample = 255 - *sample);
+        }
+        result.truncate(size);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jpeg_encoder::{ColorType, Encoder};
+
+    use super::*;
+    use crate::parser::{
+        raster_buffer::RasterBuffer,
+        tags::{TAG_BITS_PER_SAMPLE, TAG_COMPRESSION, TAG_SAMPLES_PER_PIXEL},
+        test_tiff::{write_tiff, TestIfd},
+        TiffFile,
+    };
+
+    const MARKER_SOI: u8 = 0xd8;
+    const MARKER_EOI: u8 = 0xd9;
+    const MARKER_SOS: u8 = 0xda;
+    const MARKER_DQT: u8 = 0xdb;
+    const MARKER_DHT: u8 = 0xc4;
+
+    fn encode(data: &[u8], width: u16, height: u16, color_type: ColorType) -> Vec<u8> {
+        let mut stream = vec![];
+        Encoder::new(&mut stream, 100)
+            .encode(data, width, height, color_type)
+            .unwrap();
+        stream
+    }
+
+    /// Moves the quantization and Huffman tables of a JPEG stream to a separate abbreviated
+    /// stream, as writers of JPEGTables do, returning the tables and what remains.
+    fn split_tables(stream: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let (mut tables, mut rest) = (vec![0xff, MARKER_SOI], vec![0xff, MARKER_SOI]);
+        let mut pos = 2;
+        while stream[pos + 1] != MARKER_SOS {
+            let len = u16::from_be_bytes([stream[pos + 2], stream[pos + 3]]) as usize;
+            let segment = &stream[pos..pos + 2 + len];
+            match stream[pos + 1] {
+                MARKER_DQT | MARKER_DHT => tables.extend_from_slice(segment),
+                _ => rest.extend_from_slice(segment),
+            }
+            pos += 2 + len;
+        }
+        tables.extend([0xff, MARKER_EOI]);
+        rest.extend_from_slice(&stream[pos..]);
+        (tables, rest)
+    }
+
+    fn decode(ifd: TestIfd) -> Vec<u8> {
+        let tiff = TiffFile::from_bytes(&write_tiff(&[ifd])).unwrap();
+        match tiff.ifds()[0].data() {
+            Some(RasterBuffer::U8(samples)) => samples.clone(),
+            data => panic!("unexpected data {data:?}"),
+        }
+    }
+
+    fn assert_close(samples: &[u8], expected: &[u8]) {
+        assert_eq!(samples.len(), expected.len());
+        for (sample, expected) in samples.iter().zip(expected) {
+            assert!(
+                sample.abs_diff(*expected) <= 2,
+                "{samples:?} != {expected:?}"
+            );
+        }
+    }
+
+    /// An 8 by 8 greyscale image stored as a single JPEG compressed strip.
+    fn greyscale(stream: Vec<u8>) -> TestIfd {
+        TestIfd::stripped(8, 8, 8)
+            .short(TAG_COMPRESSION, &[7])
+            .strips(vec![stream])
+    }
+
+    #[test]
+    fn full_stream() {
+        let pixels = [100; 64];
+        let samples = decode(greyscale(encode(&pixels, 8, 8, ColorType::Luma)));
+        assert_close(&samples, &pixels);
+    }
+
+    #[test]
+    fn tables_in_jpeg_tables() {
+        let pixels = [40; 64];
+        let (tables, stream) = split_tables(&encode(&pixels, 8, 8, ColorType::Luma));
+        let ifd = greyscale(stream.clone()).undefined(TAG_JPEG_TABLES, &tables);
+        assert_close(&decode(ifd), &pixels);
+        // the abbreviated stream can't be decoded on its own
+        assert!(TiffFile::from_bytes(&write_tiff(&[greyscale(stream)])).is_err());
+    }
+
+    #[test]
+    fn ycbcr_is_converted_to_rgb() {
+        let pixels = [[200, 30, 30], [30, 200, 30], [30, 30, 200], [250, 250, 250]]
+            .iter()
+            .flat_map(|rgb| rgb.repeat(64))
+            .collect::<Vec<_>>();
+        let ifd = TestIfd::stripped(8, 32, 32)
+            .short(TAG_COMPRESSION, &[7])
+            .short(TAG_PHOTOMETRIC_INTERPRETATION, &[PHOTOMETRIC_YCBCR])
+            .short(TAG_SAMPLES_PER_PIXEL, &[3])
+            .short(TAG_BITS_PER_SAMPLE, &[8, 8, 8])
+            .strips(vec![encode(&pixels, 8, 32, ColorType::Rgb)]);
+        assert_close(&decode(ifd), &pixels);
+    }
+}
@@ -0,0 +1,165 @@
+use jxl_oxide::{FrameBufferSample, ImageStream, JxlImage};
+
+use super::{
+    super::{endianness::Endianness, ifd::Ifd, TiffParserError},
+    Decompressor,
+};
+
+const SAMPLE_FORMAT_UINT: u16 = 1;
+const SAMPLE_FORMAT_IEEE_FP: u16 = 3;
+const SAMPLE_FORMAT_UNDEFINED: u16 = 4;
+
+pub(super) struct JxlDecompressor {
+    endianness: Endianness,
+    bits_per_sample: u16,
+    samples_per_pixel: usize,
+    /// The number of pixels of a whole strip or tile, which the decoded image mustn't exceed.
+    max_pixels: usize,
+}
+
+impl JxlDecompressor {
+    pub(super) fn new(endianness: Endianness, ifd: &Ifd) -> Result<Self, TiffParserError> {
+        // the decoder renders unsigned integers scaled to the range of their type, or
+        // single-precision floating-point samples
+        let (sample_format, bits_per_sample) = (ifd.sample_format()?, ifd.bits_per_sample()?);
+        match (sample_format, bits_per_sample) {
+            (SAMPLE_FORMAT_UINT | SAMPLE_FORMAT_UNDEFINED, 8 | 16)
+            | (SAMPLE_FORMAT_IEEE_FP, 32) => {}
+            _ => {
+                return Err(TiffParserError::UnsupportedSampleFormat(
+                    sample_format,
+                    bits_per_sample,
+                ));
+            }
+        }
+        let (width, length) = ifd.chunk_dimensions(ifd.is_tiled())?;
+        Ok(Self {
+            endianness,
+            bits_per_sample,
+            samples_per_pixel: ifd.samples_per_pixel()? as usize,
+            max_pixels: width.saturating_mul(length),
+        })
+    }
+
+    /// Renders the samples of the image, keeping the first `samples_per_pixel` channels of
+    /// every pixel.
+    fn render<T: FrameBufferSample + Default + Copy>(
+        &self,
+        stream: &mut ImageStream,
+    ) -> Result<Vec<T>, TiffParserError> {
+        let channels = stream.channels() as usize;
+        if channels < self.samples_per_pixel {
+            return Err(TiffParserError::Jxl(
+                "the image has fewer channels than samples per pixel".into(),
+            ));
+        }
+        let mut samples =
+            vec![T::default(); stream.width() as usize * stream.height() as usize * channels];
+        stream.write_to_buffer(&mut samples);
+        if channels == self.samples_per_pixel {
+            return Ok(samples);
+        }
+        Ok(samples
+            .chunks_exact(channels)
+            .flat_map(|pixel| &pixel[..self.samples_per_pixel])
+            .copied()
+            .collect())
+    }
+}
+
+macro_rules! to_bytes {
+    ($vals:expr, $endianness:expr) => {
+        match $endianness {
+            Endianness::LittleEndian => $vals.iter().flat_map(|val| val.to_le_bytes()).collect(),
+            Endianness::BigEndian => $vals.iter().flat_map(|val| val.to_be_bytes()).collect(),
+        }
+    };
+}
+
+impl Decompressor for JxlDecompressor {
+    fn decompress(&mut self, bytes: &[u8], size: usize) -> Result<Vec<u8>, TiffParserError> {
+        let image = JxlImage::builder().read(bytes)?;
+        // check the size declared by the stream before rendering the image
+        if image.width() as usize * image.height() as usize > self.max_pixels {
+            return Err(TiffParserError::Jxl(
+                "the image is larger than a strip or tile".into(),
+            ));
+        }
+        let render = image.render_frame(0)?;
+        let mut stream = render.stream();
+
+        // the type of the samples was checked against the sample format of the file when the
+        // decompressor was created
+        let mut result: Vec<u8> = match self.bits_per_sample {
+            8 => self.render::<u8>(&mut stream)?,
+            16 => to_bytes!(self.render::<u16>(&mut stream)?, self.endianness),
+            _ => to_bytes!(self.render::<f32>(&mut stream)?, self.endianness),
+        };
+        result.truncate(size);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{
+        raster_buffer::RasterBuffer,
+        tags::{
+            TAG_BITS_PER_SAMPLE, TAG_COMPRESSION, TAG_PHOTOMETRIC_INTERPRETATION,
+            TAG_SAMPLES_PER_PIXEL, TAG_SAMPLE_FORMAT,
+        },
+        test_tiff::{write_tiff, TestIfd},
+        TiffFile,
+    };
+
+    /// A 240 by 135 pixel RGB image with 8-bit samples, from the documentation of jxl-oxide.
+    const IMAGE: [u8; 42] = [
+        0xff, 0x0a, 0x30, 0x54, 0x10, 0x09, 0x08, 0x06, 0x01, 0x00, 0x78, 0x00, 0x4b, 0x38, 0x41,
+        0x3c, 0xb6, 0x3a, 0x51, 0xfe, 0x00, 0x47, 0x1e, 0xa0, 0x85, 0xb8, 0x27, 0x1a, 0x48, 0x45,
+        0x84, 0x1b, 0x71, 0x4f, 0xa8, 0x3e, 0x8e, 0x30, 0x03, 0x92, 0x84, 0x01,
+    ];
+
+    fn ifd(samples_per_pixel: u16) -> TestIfd {
+        TestIfd::stripped(240, 135, 135)
+            .short(TAG_COMPRESSION, &[50002])
+            .short(TAG_PHOTOMETRIC_INTERPRETATION, &[2])
+            .short(TAG_SAMPLES_PER_PIXEL, &[samples_per_pixel])
+            .short(TAG_BITS_PER_SAMPLE, &vec![8; samples_per_pixel as usize])
+            .strips(vec![IMAGE.to_vec()])
+    }
+
+    fn decode(ifd: TestIfd) -> Result<Vec<u8>, TiffParserError> {
+        let tiff = TiffFile::from_bytes(&write_tiff(&[ifd]))?;
+        match tiff.ifds()[0].data() {
+            Some(RasterBuffer::U8(samples)) => Ok(samples.clone()),
+            data => panic!("unexpected data {data:?}"),
+        }
+    }
+
+    #[test]
+    fn rgb() {
+        let samples = decode(ifd(3)).unwrap();
+        assert_eq!(samples.len(), 240 * 135 * 3);
+        assert_eq!(samples[..6], [6, 6, 6, 12, 12, 12]);
+    }
+
+    #[test]
+    fn extra_channels_are_dropped() {
+        let samples = decode(ifd(1)).unwrap();
+        assert_eq!(samples.len(), 240 * 135);
+        assert_eq!(samples[..3], [6, 12, 18]);
+        assert!(matches!(decode(ifd(4)), Err(TiffParserError::Jxl(_))));
+    }
+
+    #[test]
+    fn unsupported_sample_formats() {
+        let ifd = ifd(1)
+            .short(TAG_BITS_PER_SAMPLE, &[32])
+            .short(TAG_SAMPLE_FORMAT, &[2]);
+        assert!(matches!(
+            decode(ifd),
+            Err(TiffParserError::UnsupportedSampleFormat(2, 32))
+        ));
+    }
+}
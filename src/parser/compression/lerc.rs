@@ -0,0 +1,165 @@
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+use lerc::{LercError, SampleData};
+
+use super::{
+    super::{endianness::Endianness, ifd::Ifd, tags::TAG_LERC_PARAMETERS, TiffParserError},
+    Decompressor, ZstdDecompressor,
+};
+
+const LERC_ADD_COMPRESSION_NONE: u32 = 0;
+const LERC_ADD_COMPRESSION_DEFLATE: u32 = 1;
+const LERC_ADD_COMPRESSION_ZSTD: u32 = 2;
+
+const MAX_BLOB_OVERHEAD: usize = 1 << 16;
+
+pub(super) struct LercDecompressor {
+    endianness: Endianness,
+    additional_compression: u32,
+}
+
+impl LercDecompressor {
+    pub(super) fn new(endianness: Endianness, ifd: &Ifd) -> Result<Self, TiffParserError> {
+        // LercParameters contains the LERC version followed by the additional compression
+        let additional_compression = match ifd.get_value(TAG_LERC_PARAMETERS) {
+            Ok(value) => value
+                .longs()?
+                .get(1)
+                .copied()
+                .unwrap_or(LERC_ADD_COMPRESSION_NONE),
+            Err(_) => LERC_ADD_COMPRESSION_NONE,
+        };
+        Ok(Self {
+            endianness,
+            additional_compression,
+        })
+    }
+}
+
+macro_rules! to_bytes {
+    ($vals:expr, $endianness:expr) => {
+        match $endianness {
+            Endianness::LittleEndian => $vals.iter().flat_map(|val| val.to_le_bytes()).collect(),
+            Endianness::BigEndian => $vals.iter().flat_map(|val| val.to_be_bytes()).collect(),
+        }
+    };
+}
+
+impl Decompressor for LercDecompressor {
+    fn decompress(&mut self, bytes: &[u8], size: usize) -> Result<Vec<u8>, TiffParserError> {
+        // a LERC blob holds the samples along with its headers and a mask of the valid pixels,
+        // and is never much larger than the uncompressed samples
+        let max_blob_size = size.saturating_mul(2).saturating_add(MAX_BLOB_OVERHEAD);
+        let blob = match self.additional_compression {
+            LERC_ADD_COMPRESSION_NONE => bytes.to_vec(),
+            LERC_ADD_COMPRESSION_DEFLATE => {
+                let mut blob = vec![];
+                ZlibDecoder::new(bytes)
+                    .take(max_blob_size as u64)
+                    .read_to_end(&mut blob)?;
+                blob
+            }
+            LERC_ADD_COMPRESSION_ZSTD => {
+                ZstdDecompressor::decompress_limited(bytes, max_blob_size)?
+            }
+            compression => {
+                return Err(TiffParserError::UnsupportedLercCompression(compression));
+            }
+        };
+
+        // check the size declared by the blob before decoding the image
+        let info = lerc::decode_info(&blob)?;
+        let decoded_size = [info.height, info.depth, info.bands]
+            .into_iter()
+            .try_fold(info.width as usize, |acc, dim| {
+                acc.checked_mul(dim as usize)
+            })
+            .and_then(|samples| samples.checked_mul(info.data_type.size()));
+        if decoded_size != Some(size) {
+            return Err(TiffParserError::Lerc(LercError::InvalidData(
+                "the image doesn't match the strip or tile".into(),
+            )));
+        }
+
+        let mut image = lerc::decode(&blob)?;
+        // GDAL marks the pixels excluded by the LERC mask as NaN in floating-point rasters
+        let depth = image.depth as usize;
+        let mask = image.valid_masks.first();
+        let is_valid = |index: usize| mask.is_none_or(|mask| mask.is_valid(index / depth));
+        match &mut image.data {
+            SampleData::F32(vals) => vals
+                .iter_mut()
+                .enumerate()
+                .filter(|(index, _)| !is_valid(*index))
+                .for_each(|(_, val)| *val = f32::NAN),
+            SampleData::F64(vals) => vals
+                .iter_mut()
+                .enumerate()
+                .filter(|(index, _)| !is_valid(*index))
+                .for_each(|(_, val)| *val = f64::NAN),
+            _ => {}
+        }
+
+        let mut result: Vec<u8> = match &image.data {
+            SampleData::I8(vals) => vals.iter().map(|val| *val as u8).collect(),
+            SampleData::U8(vals) => vals.clone(),
+            SampleData::I16(vals) => to_bytes!(vals, self.endianness),
+            SampleData::U16(vals) => to_bytes!(vals, self.endianness),
+            SampleData::I32(vals) => to_bytes!(vals, self.endianness),
+            SampleData::U32(vals) => to_bytes!(vals, self.endianness),
+            SampleData::F32(vals) => to_bytes!(vals, self.endianness),
+            SampleData::F64(vals) => to_bytes!(vals, self.endianness),
+        };
+        result.truncate(size);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lerc::{Image, Precision};
+
+    use super::*;
+
+    fn blob(width: u32, height: u32, samples: Vec<f32>) -> Vec<u8> {
+        let image = Image::from_pixels(width, height, samples).unwrap();
+        lerc::encode(&image, Precision::Lossless).unwrap()
+    }
+
+    fn decompressor(endianness: Endianness) -> LercDecompressor {
+        LercDecompressor {
+            endianness,
+            additional_compression: LERC_ADD_COMPRESSION_NONE,
+        }
+    }
+
+    #[test]
+    fn samples_in_the_byte_order_of_the_file() {
+        let samples = vec![1.5, -2.0, 0.25, 1e10];
+        let blob = blob(2, 2, samples.clone());
+        let expected = samples.iter().flat_map(|sample| sample.to_be_bytes());
+        assert_eq!(
+            decompressor(Endianness::BigEndian)
+                .decompress(&blob, 16)
+                .unwrap(),
+            expected.collect::<Vec<_>>()
+        );
+        let expected = samples.iter().flat_map(|sample| sample.to_le_bytes());
+        assert_eq!(
+            decompressor(Endianness::LittleEndian)
+                .decompress(&blob, 16)
+                .unwrap(),
+            expected.collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn images_not_matching_the_chunk_are_rejected() {
+        let blob = blob(64, 64, vec![0.0; 64 * 64]);
+        assert!(matches!(
+            decompressor(Endianness::LittleEndian).decompress(&blob, 16),
+            Err(TiffParserError::Lerc(LercError::InvalidData(_)))
+        ));
+    }
+}
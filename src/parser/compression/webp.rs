@@ -0,0 +1,82 @@
+use std::io::Cursor;
+
+use image_webp::{DecodingError, WebPDecoder};
+
+use super::{
+    super::{ifd::Ifd, TiffParserError},
+    Decompressor,
+};
+
+pub(super) struct WebPDecompressor {
+    samples_per_pixel: usize,
+    /// The number of pixels of a whole strip or tile, which the decoded image mustn't exceed.
+    max_pixels: usize,
+}
+
+impl WebPDecompressor {
+    pub(super) fn new(ifd: &Ifd) -> Result<Self, TiffParserError> {
+        let (width, length) = ifd.chunk_dimensions(ifd.is_tiled())?;
+        Ok(Self {
+            samples_per_pixel: ifd.samples_per_pixel()? as usize,
+            max_pixels: width.saturating_mul(length),
+        })
+    }
+}
+
+impl Decompressor for WebPDecompressor {
+    fn decompress(&mut self, bytes: &[u8], size: usize) -> Result<Vec<u8>, TiffParserError> {
+        let mut decoder = WebPDecoder::new(Cursor::new(bytes))?;
+        // check the size declared by the stream before allocating the image
+        let (width, height) = decoder.dimensions();
+        if width as usize * height as usize > self.max_pixels {
+            return Err(DecodingError::ImageTooLarge.into());
+        }
+        let mut image = vec![0; decoder.output_buffer_size().unwrap_or(0)];
+        decoder.read_image(&mut image)?;
+
+        // the decoder always produces RGB or RGBA, regardless of the number of bands in the file
+        let decoded_samples = if decoder.has_alpha() { 4 } else { 3 };
+        let mut result = if decoded_samples == self.samples_per_pixel {
+            image
+        } else {
+            image
+                .chunks_exact(decoded_samples)
+                .flat_map(|pixel| {
+                    (0..self.samples_per_pixel).map(move |i| pixel.get(i).copied().unwrap_or(255))
+                })
+                .collect()
+        };
+        result.truncate(size);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image_webp::{ColorType, WebPEncoder};
+
+    use super::*;
+
+    fn encode(width: u32, height: u32) -> Vec<u8> {
+        let pixels: Vec<u8> = (0..width * height * 3).map(|i| i as u8).collect();
+        let mut webp = vec![];
+        WebPEncoder::new(&mut webp)
+            .encode(&pixels, width, height, ColorType::Rgb8)
+            .unwrap();
+        webp
+    }
+
+    #[test]
+    fn images_larger_than_a_tile_are_rejected() {
+        let mut decompressor = WebPDecompressor {
+            samples_per_pixel: 3,
+            max_pixels: 4,
+        };
+        let pixels: Vec<u8> = (0..12).collect();
+        assert_eq!(decompressor.decompress(&encode(2, 2), 12).unwrap(), pixels);
+        assert!(matches!(
+            decompressor.decompress(&encode(4, 4), 12),
+            Err(TiffParserError::WebP(DecodingError::ImageTooLarge))
+        ));
+    }
+}
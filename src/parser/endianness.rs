@@ -2,15 +2,16 @@ use std::convert::TryFrom;
 
 use super::TiffParserError;
 
-#[derive(Debug, Clone, Copy)]
-pub(super) enum Endianness {
+/// The byte order of a TIFF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
     LittleEndian,
     BigEndian,
 }
 
 impl Endianness {
     pub(super) fn read_i16(&self, buf: &[u8]) -> Result<i16, TiffParserError> {
-        let bytes = <[u8; 2]>::try_from(&buf[0..2])?;
+        let bytes = <[u8; 2]>::try_from(head(buf, 2))?;
         let val = match self {
             Endianness::LittleEndian => i16::from_le_bytes(bytes),
             Endianness::BigEndian => i16::from_be_bytes(bytes),
@@ -19,7 +20,7 @@ impl Endianness {
     }
 
     pub(super) fn read_u16(&self, buf: &[u8]) -> Result<u16, TiffParserError> {
-        let bytes = <[u8; 2]>::try_from(&buf[0..2])?;
+        let bytes = <[u8; 2]>::try_from(head(buf, 2))?;
         let val = match self {
             Endianness::LittleEndian => u16::from_le_bytes(bytes),
             Endianness::BigEndian => u16::from_be_bytes(bytes),
@@ -28,7 +29,7 @@ impl Endianness {
     }
 
     pub(super) fn read_i32(&self, buf: &[u8]) -> Result<i32, TiffParserError> {
-        let bytes = <[u8; 4]>::try_from(&buf[0..4])?;
+        let bytes = <[u8; 4]>::try_from(head(buf, 4))?;
         let val = match self {
             Endianness::LittleEndian => i32::from_le_bytes(bytes),
             Endianness::BigEndian => i32::from_be_bytes(bytes),
@@ -37,7 +38,7 @@ impl Endianness {
     }
 
     pub(super) fn read_u32(&self, buf: &[u8]) -> Result<u32, TiffParserError> {
-        let bytes = <[u8; 4]>::try_from(&buf[0..4])?;
+        let bytes = <[u8; 4]>::try_from(head(buf, 4))?;
         let val = match self {
             Endianness::LittleEndian => u32::from_le_bytes(bytes),
             Endianness::BigEndian => u32::from_be_bytes(bytes),
@@ -45,8 +46,26 @@ impl Endianness {
         Ok(val)
     }
 
+    pub(super) fn read_i64(&self, buf: &[u8]) -> Result<i64, TiffParserError> {
+        let bytes = <[u8; 8]>::try_from(head(buf, 8))?;
+        let val = match self {
+            Endianness::LittleEndian => i64::from_le_bytes(bytes),
+            Endianness::BigEndian => i64::from_be_bytes(bytes),
+        };
+        Ok(val)
+    }
+
+    pub(super) fn read_u64(&self, buf: &[u8]) -> Result<u64, TiffParserError> {
+        let bytes = <[u8; 8]>::try_from(head(buf, 8))?;
+        let val = match self {
+            Endianness::LittleEndian => u64::from_le_bytes(bytes),
+            Endianness::BigEndian => u64::from_be_bytes(bytes),
+        };
+        Ok(val)
+    }
+
     pub(super) fn read_f32(&self, buf: &[u8]) -> Result<f32, TiffParserError> {
-        let bytes = <[u8; 4]>::try_from(&buf[0..4])?;
+        let bytes = <[u8; 4]>::try_from(head(buf, 4))?;
         let val = match self {
             Endianness::LittleEndian => f32::from_le_bytes(bytes),
             Endianness::BigEndian => f32::from_be_bytes(bytes),
@@ -55,7 +74,7 @@ impl Endianness {
     }
 
     pub(super) fn read_f64(&self, buf: &[u8]) -> Result<f64, TiffParserError> {
-        let bytes = <[u8; 8]>::try_from(&buf[0..8])?;
+        let bytes = <[u8; 8]>::try_from(head(buf, 8))?;
         let val = match self {
             Endianness::LittleEndian => f64::from_le_bytes(bytes),
             Endianness::BigEndian => f64::from_be_bytes(bytes),
@@ -63,3 +82,9 @@ impl Endianness {
         Ok(val)
     }
 }
+
+/// The first `len` bytes of `buf`, or the whole of it if it's shorter, in which case the
+/// conversion to an array fails.
+fn head(buf: &[u8], len: usize) -> &[u8] {
+    buf.get(..len).unwrap_or(buf)
+}
@@ -15,18 +15,84 @@ pub enum TiffParserError {
     Io(#[from] IoError),
     #[error("A string wasn't null-terminated: {0:?}")]
     StringNotNullTerminated(Vec<u8>),
+    #[error("Reading {1} bytes at offset {0} goes beyond the end of the file")]
+    OutOfBounds(usize, usize),
+    #[error("The value of tag {0} at offset {1} ({2} bytes) goes beyond the end of the file")]
+    EntryOutOfBounds(u16, usize, usize),
+    #[error("The IFD at offset {0} is referenced more than once")]
+    IfdLoop(usize),
+    #[error("SubIFDs are nested more than {0} levels deep")]
+    SubIfdsTooDeep(usize),
     #[error("Unknown field type: {0}")]
     UnknownFieldType(u16),
     #[error("Unknown endianness marker: {0:?}")]
     UnknownEndiannessMarker(Vec<u8>),
+    #[error("Unknown TIFF version: {0}")]
+    UnknownVersion(u16),
+    #[error("Unsupported BigTIFF offset size: {0}")]
+    UnsupportedOffsetSize(u16),
     #[error("The image has both tile and strip data")]
     ImageBothTiledAndStripped,
     #[error("Invalid value {0:?}, {1}")]
     InvalidValue(Value, &'static str),
     #[error("Missing value for tag {0}")]
     MissingValue(u16),
+    #[error("There is no IFD {0}")]
+    MissingIfd(usize),
+    #[error("There is no strip or tile {0}")]
+    MissingChunk(usize),
+    #[error("Strip or tile {0} at offset {1} ({2} bytes) goes beyond the end of the file")]
+    ChunkOutOfBounds(usize, u64, u64),
+    #[error("Invalid strip or tile {0}: {1}")]
+    InvalidChunk(usize, &'static str),
+    #[error("There is no band {0}")]
+    MissingBand(usize),
+    #[cfg(feature = "mmap")]
+    #[error("The file isn't memory-mapped")]
+    NotMapped,
+    #[cfg(feature = "mmap")]
+    #[error("Samples can only be read in place from uncompressed images")]
+    CompressedImage,
+    #[cfg(feature = "http")]
+    #[error("HTTP error: {0}")]
+    Http(Box<ureq::Error>),
+    #[error("The window of {2}x{3} pixels at ({0}, {1}) extends beyond the image")]
+    InvalidWindow(usize, usize, usize, usize),
+    #[error("Invalid GeoKey {0}: {1}")]
+    InvalidGeoKey(u16, &'static str),
+    #[error("The file contains no georeferencing information")]
+    MissingGeoreferencing,
+    #[error("Invalid georeferencing: {0}")]
+    InvalidGeoreferencing(&'static str),
+    #[error("Invalid GDAL metadata XML: {0}")]
+    GdalMetadata(#[from] roxmltree::Error),
+    #[error("Unsupported sample format {0} with {1} bits per sample")]
+    UnsupportedSampleFormat(u16, u16),
     #[error("Unknown compression scheme: {0}")]
     UnknownCompression(u16),
+    #[error("Only 8-bit YCbCr data with 3 samples per pixel is supported")]
+    UnsupportedYCbCr,
+    #[error("Unsupported predictor: {0}")]
+    UnsupportedPredictor(u16),
     #[error("LZW decompression error: {0}")]
     Lzw(#[from] LzwError),
+    #[error("CCITT decompression error: {0}")]
+    Ccitt(&'static str),
+    #[error("JPEG decompression error: {0}")]
+    Jpeg(#[from] jpeg_decoder::Error),
+    #[cfg(feature = "zstd")]
+    #[error("Zstandard decompression error: {0}")]
+    Zstd(#[from] ruzstd::decoding::errors::FrameDecoderError),
+    #[cfg(feature = "webp")]
+    #[error("WebP decompression error: {0}")]
+    WebP(#[from] image_webp::DecodingError),
+    #[cfg(feature = "jxl")]
+    #[error("JPEG-XL decompression error: {0}")]
+    Jxl(#[from] Box<dyn std::error::Error + Send + Sync>),
+    #[cfg(feature = "lerc")]
+    #[error("LERC decompression error: {0}")]
+    Lerc(#[from] lerc::LercError),
+    #[cfg(feature = "lerc")]
+    #[error("Unsupported additional compression in LERC data: {0}")]
+    UnsupportedLercCompression(u32),
 }
@@ -14,6 +14,10 @@ pub(crate) enum Field {
     Srational = 10,
     Float = 11,
     Double = 12,
+    Ifd = 13,
+    Long8 = 16,
+    Slong8 = 17,
+    Ifd8 = 18,
 }
 
 impl Field {
@@ -31,6 +35,10 @@ impl Field {
             10 => Ok(Field::Srational),
             11 => Ok(Field::Float),
             12 => Ok(Field::Double),
+            13 => Ok(Field::Ifd),
+            16 => Ok(Field::Long8),
+            17 => Ok(Field::Slong8),
+            18 => Ok(Field::Ifd8),
             field => Err(TiffParserError::UnknownFieldType(field)),
         }
     }
@@ -39,8 +47,13 @@ impl Field {
         match self {
             Field::Byte | Field::Ascii | Field::Sbyte | Field::Undefined => 1,
             Field::Short | Field::Sshort => 2,
-            Field::Long | Field::Slong | Field::Float => 4,
-            Field::Rational | Field::Srational | Field::Double => 8,
+            Field::Long | Field::Slong | Field::Float | Field::Ifd => 4,
+            Field::Rational
+            | Field::Srational
+            | Field::Double
+            | Field::Long8
+            | Field::Slong8
+            | Field::Ifd8 => 8,
         }
     }
 }
@@ -1,10 +1,40 @@
-use std::fmt;
+use std::{collections::HashSet, fmt};
 
 use super::{
-    compression::create_decompressor, endianness::Endianness, field::Field, tags::*, value::Value,
+    compression::{DecompressorRegistry, COMPRESSION_JPEG},
+    endianness::Endianness,
+    field::Field,
+    predictor::reverse_predictor,
+    raster_buffer::{RasterBuffer, RasterValue},
+    source::Source,
+    tags::*,
+    value::Value,
+    variant::TiffVariant,
+    ycbcr::YCbCrConverter,
     TiffParserError,
 };
 
+const PHOTOMETRIC_WHITE_IS_ZERO: u16 = 0;
+const PHOTOMETRIC_BLACK_IS_ZERO: u16 = 1;
+const PHOTOMETRIC_YCBCR: u16 = 6;
+
+// NewSubfileType flags
+const SUBFILE_REDUCED_RESOLUTION: u32 = 1;
+const SUBFILE_MASK: u32 = 4;
+
+const PLANAR_CONFIGURATION_CHUNKY: u16 = 1;
+const PLANAR_CONFIGURATION_PLANAR: u16 = 2;
+
+const FILL_ORDER_MSB_FIRST: u16 = 1;
+const FILL_ORDER_LSB_FIRST: u16 = 2;
+
+/// The largest decoded strip or tile accepted, so that a corrupted file can't make the decoder
+/// allocate an unreasonable amount of memory.
+const MAX_CHUNK_SIZE: usize = 1 << 30;
+
+/// The deepest nesting of SubIFDs accepted, so that a corrupted file can't overflow the stack.
+const MAX_SUB_IFD_DEPTH: usize = 16;
+
 #[derive(Debug)]
 pub struct IfdEntry {
     pub(super) tag: u16,
@@ -12,44 +42,210 @@ pub struct IfdEntry {
 }
 
 impl IfdEntry {
-    fn read(endianness: Endianness, buf: &[u8], start: usize) -> Result<Self, TiffParserError> {
-        let tag = endianness.read_u16(&buf[start..])?;
-        let field = Field::from_u16(endianness.read_u16(&buf[start + 2..])?)?;
-        let count = endianness.read_u32(&buf[start + 4..])? as usize;
-        let num_bytes = count * field.size();
-        let bytes = if num_bytes <= 4 {
-            &buf[start + 8..start + 8 + num_bytes]
-        } else {
-            let offset = endianness.read_u32(&buf[start + 8..])? as usize;
-            &buf[offset..offset + num_bytes]
+    /// Reads the entry stored in `buf`, fetching its value from `source` if it doesn't fit in
+    /// the entry.
+    fn read<S: Source>(
+        endianness: Endianness,
+        variant: TiffVariant,
+        buf: &[u8],
+        source: &mut S,
+    ) -> Result<Self, TiffParserError> {
+        let tag = endianness.read_u16(buf)?;
+        let field = Field::from_u16(endianness.read_u16(&buf[2..])?)?;
+        let count = variant.read_offset(endianness, &buf[4..])?;
+        let value_start = 4 + variant.offset_size();
+        let num_bytes = count.checked_mul(field.size());
+        let value = match num_bytes {
+            Some(num_bytes) if num_bytes <= variant.offset_size() => Value::from_bytes(
+                endianness,
+                field,
+                &buf[value_start..value_start + num_bytes],
+            )?,
+            _ => {
+                let offset = variant.read_offset(endianness, &buf[value_start..])?;
+                let num_bytes = num_bytes.unwrap_or(usize::MAX);
+                let bytes = match source.read_bytes(offset, num_bytes) {
+                    Err(TiffParserError::OutOfBounds(..)) => {
+                        return Err(TiffParserError::EntryOutOfBounds(tag, offset, num_bytes));
+                    }
+                    bytes => bytes?,
+                };
+                Value::from_bytes(endianness, field, &bytes)?
+            }
         };
-        let value = Value::from_bytes(endianness, field, bytes)?;
         Ok(IfdEntry { tag, value })
     }
 }
 
+/// The arrangement of the strips or tiles an image is divided into. Strips are treated as tiles
+/// spanning the whole width of the image.
+///
+/// With PlanarConfiguration 2 each band is stored in a separate plane of chunks, the chunks of
+/// the first band being followed by those of the second one and so on.
+#[derive(Debug)]
+pub(super) struct ChunkLayout {
+    pub(super) image_width: usize,
+    pub(super) image_length: usize,
+    pub(super) chunk_width: usize,
+    pub(super) chunk_length: usize,
+    pub(super) chunks_across: usize,
+    pub(super) chunks_per_plane: usize,
+    /// The number of planes, 1 unless the bands are stored separately.
+    pub(super) planes: usize,
+    /// The number of samples of each pixel stored in a chunk.
+    pub(super) chunk_samples: usize,
+    pub(super) tiled: bool,
+    pub(super) offsets: Vec<u64>,
+    pub(super) byte_counts: Vec<u64>,
+}
+
+impl ChunkLayout {
+    pub(super) fn num_chunks(&self) -> usize {
+        self.offsets.len().min(self.byte_counts.len())
+    }
+
+    pub(super) fn is_planar(&self) -> bool {
+        self.planes > 1
+    }
+
+    pub(super) fn samples_per_pixel(&self) -> usize {
+        self.planes * self.chunk_samples
+    }
+
+    /// The index of the chunk in the given plane, row and column of chunks.
+    pub(super) fn index(&self, plane: usize, chunk_row: usize, chunk_col: usize) -> usize {
+        plane * self.chunks_per_plane + chunk_row * self.chunks_across + chunk_col
+    }
+
+    /// The number of rows stored in the given chunk. All tiles have the same size, but the last
+    /// strip of each plane may contain fewer rows than the others.
+    pub(super) fn rows(&self, index: usize) -> usize {
+        if self.tiled {
+            self.chunk_length
+        } else {
+            self.image_length
+                .saturating_sub(index % self.chunks_per_plane * self.chunk_length)
+                .min(self.chunk_length)
+        }
+    }
+
+    /// The planes holding the given band, or all bands if `band` is `None`.
+    fn planes_of(&self, band: Option<usize>) -> Result<std::ops::Range<usize>, TiffParserError> {
+        match band {
+            Some(band) if band >= self.samples_per_pixel() => {
+                Err(TiffParserError::MissingBand(band))
+            }
+            Some(band) if self.is_planar() => Ok(band..band + 1),
+            _ => Ok(0..if self.is_planar() { self.planes } else { 1 }),
+        }
+    }
+
+    /// Returns the indices of the chunks needed to read the given band, or all bands if `band`
+    /// is `None`, within the window of `width` by `height` pixels whose top-left corner is at
+    /// column `x_off` and row `y_off`.
+    pub(super) fn window_chunks(
+        &self,
+        band: Option<usize>,
+        x_off: usize,
+        y_off: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<Vec<usize>, TiffParserError> {
+        let mut indices = vec![];
+        if width == 0 || height == 0 {
+            return Ok(indices);
+        }
+        for plane in self.planes_of(band)? {
+            for chunk_row in y_off / self.chunk_length..=(y_off + height - 1) / self.chunk_length {
+                for chunk_col in x_off / self.chunk_width..=(x_off + width - 1) / self.chunk_width {
+                    indices.push(self.index(plane, chunk_row, chunk_col));
+                }
+            }
+        }
+        Ok(indices)
+    }
+
+    /// Copies the pixels of the given plane within a window out of the decoded chunks, each
+    /// pixel taking `pixel_size` bytes.
+    fn copy_window<'a, F: Fn(usize) -> Option<&'a [u8]>>(
+        &self,
+        chunk: &F,
+        plane: usize,
+        (x_off, y_off, width, height): (usize, usize, usize, usize),
+        pixel_size: usize,
+    ) -> Result<Vec<u8>, TiffParserError> {
+        let mut bytes = vec![];
+        for j in y_off..y_off + height {
+            let chunk_row = j / self.chunk_length;
+            let jc = j - chunk_row * self.chunk_length;
+            let mut i = x_off;
+            while i < x_off + width {
+                // tiles in the last column may extend beyond the edge of the image
+                let chunk_col = i / self.chunk_width;
+                let ic = i - chunk_col * self.chunk_width;
+                let n = (self.chunk_width - ic).min(x_off + width - i);
+                let index = self.index(plane, chunk_row, chunk_col);
+                let chunk = chunk(index).ok_or(TiffParserError::MissingChunk(index))?;
+                let start = (jc * self.chunk_width + ic) * pixel_size;
+                bytes.extend_from_slice(&chunk[start..start + n * pixel_size]);
+                i += n;
+            }
+        }
+        Ok(bytes)
+    }
+}
+
 pub struct Ifd {
     pub entries: Vec<IfdEntry>,
     pub(super) sub_ifds: Vec<Ifd>,
-    pub(crate) data: Vec<i32>,
+    data: Option<RasterBuffer>,
 }
 
 impl Ifd {
-    pub(super) fn read(
+    /// Reads the entries of the IFD starting at `start`, and those of its SubIFDs, without
+    /// decoding any image data. Returns the IFD and the offset of the next one.
+    ///
+    /// `visited` holds the offsets of the IFDs read so far, so that an IFD referring back to
+    /// one of them is detected instead of being read over and over. `depth` is the number of
+    /// IFDs the IFD is nested in as a SubIFD, 0 for top-level IFDs.
+    pub(super) fn read<S: Source>(
         endianness: Endianness,
-        buf: &[u8],
+        variant: TiffVariant,
+        source: &mut S,
         start: usize,
+        visited: &mut HashSet<usize>,
+        depth: usize,
     ) -> Result<(Self, usize), TiffParserError> {
-        let num_entries = endianness.read_u16(&buf[start..])? as usize;
+        if !visited.insert(start) {
+            return Err(TiffParserError::IfdLoop(start));
+        }
+        if depth > MAX_SUB_IFD_DEPTH {
+            return Err(TiffParserError::SubIfdsTooDeep(MAX_SUB_IFD_DEPTH));
+        }
+        let num_entries = variant.read_entry_count(
+            endianness,
+            &source.read_bytes(start, variant.entry_count_size())?,
+        )?;
+        // the entries are followed by the offset of the next IFD
+        let entries_start = start.saturating_add(variant.entry_count_size());
+        let entries_len = num_entries
+            .checked_mul(variant.entry_size())
+            .and_then(|len| len.checked_add(variant.offset_size()))
+            .ok_or(TiffParserError::OutOfBounds(entries_start, usize::MAX))?;
+        let buf = source.read_bytes(entries_start, entries_len)?.into_owned();
         let mut entries = vec![];
         let mut sub_ifds = vec![];
-        for i in 0..num_entries {
-            let entry = IfdEntry::read(endianness, buf, start + 2 + i * 12)?;
+        for entry_buf in buf.chunks_exact(variant.entry_size()) {
+            let entry = IfdEntry::read(endianness, variant, entry_buf, source)?;
             match (&entry.tag, &entry.value) {
-                (&TAG_SUB_IFDS, &Value::Longs(ref longs)) => {
-                    let mut offset = longs[0] as usize;
+                (
+                    &TAG_SUB_IFDS,
+                    Value::Longs(_) | Value::Ifds(_) | Value::Longs8(_) | Value::Ifds8(_),
+                ) => {
+                    let mut offset = entry.value.as_u64()? as usize;
                     while offset != 0 {
-                        let (sub_ifd, next_offset) = Ifd::read(endianness, buf, offset)?;
+                        let (sub_ifd, next_offset) =
+                            Ifd::read(endianness, variant, source, offset, visited, depth + 1)?;
                         sub_ifds.push(sub_ifd);
                         offset = next_offset;
                     }
@@ -59,112 +255,458 @@ impl Ifd {
                 }
             }
         }
-        let next_ifd_offset = endianness.read_u32(&buf[start + 2 + num_entries * 12..])? as usize;
+        let next_ifd_offset =
+            variant.read_offset(endianness, &buf[num_entries * variant.entry_size()..])?;
 
-        let mut ifd = Ifd {
+        let ifd = Ifd {
             entries,
             sub_ifds,
-            data: vec![],
+            data: None,
         };
 
-        ifd.read_data(endianness, buf)?;
-
         Ok((ifd, next_ifd_offset))
     }
 
-    pub fn image_width(&self) -> Option<u16> {
-        self.get_value(TAG_IMAGE_WIDTH).ok()?.short().ok()
+    /// The decoded samples of the image, if they have been read.
+    pub fn data(&self) -> Option<&RasterBuffer> {
+        self.data.as_ref()
     }
 
-    pub fn image_length(&self) -> Option<u16> {
-        self.get_value(TAG_IMAGE_LENGTH).ok()?.short().ok()
+    /// The IFDs listed in the SubIFDs tag of this one, such as the overviews of its image.
+    pub fn sub_ifds(&self) -> &[Ifd] {
+        &self.sub_ifds
     }
 
-    fn read_data(&mut self, endianness: Endianness, buf: &[u8]) -> Result<(), TiffParserError> {
-        if self.is_stripped() && !self.is_tiled() {
-            self.read_data_stripped(endianness, buf)
-        } else if self.is_tiled() && !self.is_stripped() {
-            self.read_data_tiled(endianness, buf)
-        } else if self.is_tiled() && self.is_stripped() {
-            Err(TiffParserError::ImageBothTiledAndStripped)
-        } else {
-            // no image data
-            Ok(())
+    pub fn image_width(&self) -> Option<u32> {
+        self.dimension(TAG_IMAGE_WIDTH).ok()
+    }
+
+    pub fn image_length(&self) -> Option<u32> {
+        self.dimension(TAG_IMAGE_LENGTH).ok()
+    }
+
+    /// Reads a dimension tag, which may be either SHORT or LONG.
+    fn dimension(&self, tag: u16) -> Result<u32, TiffParserError> {
+        let value = self.get_value(tag)?;
+        u32::try_from(value.as_u64()?)
+            .map_err(|_| TiffParserError::InvalidValue(value.clone(), "dimension out of range"))
+    }
+
+    /// Reads a dimension tag of the image or of its strips or tiles, which must be positive.
+    fn layout_dimension(&self, tag: u16) -> Result<usize, TiffParserError> {
+        match self.dimension(tag)? {
+            0 => Err(TiffParserError::InvalidValue(
+                self.get_value(tag)?.clone(),
+                "dimensions must be positive",
+            )),
+            dimension => Ok(dimension as usize),
         }
     }
 
-    fn read_data_stripped(
-        &mut self,
-        _endianness: Endianness,
-        _buf: &[u8],
-    ) -> Result<(), TiffParserError> {
-        unimplemented!()
+    /// The NewSubfileType of the image, a set of flags describing what it represents in
+    /// relation to the other images of the file.
+    pub fn new_subfile_type(&self) -> u32 {
+        // the tag is a LONG, but some writers use a SHORT
+        self.get_value(TAG_NEW_SUBFILE_TYPE)
+            .and_then(Value::as_u64)
+            .map_or(0, |flags| flags as u32)
+    }
+
+    /// Returns whether the image is a reduced-resolution version of another image in the file.
+    pub fn is_reduced_resolution(&self) -> bool {
+        self.new_subfile_type() & SUBFILE_REDUCED_RESOLUTION != 0
+    }
+
+    /// Returns whether the image is a transparency mask for another image in the file.
+    pub fn is_mask(&self) -> bool {
+        self.new_subfile_type() & SUBFILE_MASK != 0
+    }
+
+    pub fn samples_per_pixel(&self) -> Result<u16, TiffParserError> {
+        match self.get_value(TAG_SAMPLES_PER_PIXEL) {
+            Ok(value) => match value.short()? {
+                0 => Err(TiffParserError::InvalidValue(
+                    value.clone(),
+                    "there must be at least one sample per pixel",
+                )),
+                samples => Ok(samples),
+            },
+            // the default as defined in the TIFF specification
+            Err(_) => Ok(1),
+        }
+    }
+
+    fn photometric_interpretation(&self) -> Result<u16, TiffParserError> {
+        match self.get_value(TAG_PHOTOMETRIC_INTERPRETATION) {
+            Ok(value) => value.short(),
+            // there's no default, but BlackIsZero is the most sensible interpretation
+            Err(_) => Ok(PHOTOMETRIC_BLACK_IS_ZERO),
+        }
+    }
+
+    pub fn bits_per_sample(&self) -> Result<u16, TiffParserError> {
+        let value = self.get_value(TAG_BITS_PER_SAMPLE)?;
+        let bits = value.shorts()?;
+        if bits.is_empty() || bits.iter().any(|b| *b != bits[0]) {
+            return Err(TiffParserError::InvalidValue(
+                value.clone(),
+                "all samples must have the same number of bits",
+            ));
+        }
+        Ok(bits[0])
+    }
+
+    pub(super) fn sample_format(&self) -> Result<u16, TiffParserError> {
+        let value = match self.get_value(TAG_SAMPLE_FORMAT) {
+            Ok(value) => value,
+            // unsigned integer data is the default
+            Err(_) => return Ok(1),
+        };
+        let formats = value.shorts()?;
+        if formats.is_empty() || formats.iter().any(|f| *f != formats[0]) {
+            return Err(TiffParserError::InvalidValue(
+                value.clone(),
+                "all samples must have the same format",
+            ));
+        }
+        Ok(formats[0])
     }
 
-    fn read_data_tiled(
+    /// The number of bits per sample after decoding, 1-bit samples being unpacked to one byte
+    /// each.
+    fn decoded_bits_per_sample(&self) -> Result<u16, TiffParserError> {
+        match self.bits_per_sample()? {
+            1 => Ok(8),
+            bits @ (8 | 16 | 32 | 64) => Ok(bits),
+            bits => Err(TiffParserError::UnsupportedSampleFormat(
+                self.sample_format()?,
+                bits,
+            )),
+        }
+    }
+
+    /// Parses a sample of the format of the decoded image from its textual representation.
+    pub(crate) fn parse_sample(&self, string: &str) -> Option<RasterValue> {
+        RasterValue::parse(
+            self.sample_format().ok()?,
+            self.decoded_bits_per_sample().ok()?,
+            string,
+        )
+    }
+
+    fn planar_configuration(&self) -> Result<u16, TiffParserError> {
+        match self.get_value(TAG_PLANAR_CONFIGURATION) {
+            Ok(value) => value.short(),
+            // the default as defined in the TIFF specification
+            Err(_) => Ok(PLANAR_CONFIGURATION_CHUNKY),
+        }
+    }
+
+    fn fill_order(&self) -> Result<u16, TiffParserError> {
+        match self.get_value(TAG_FILL_ORDER) {
+            Ok(value) => value.short(),
+            // the default as defined in the TIFF specification
+            Err(_) => Ok(FILL_ORDER_MSB_FIRST),
+        }
+    }
+
+    pub(super) fn bytes_per_sample(&self) -> Result<usize, TiffParserError> {
+        Ok(self.decoded_bits_per_sample()? as usize / 8)
+    }
+
+    /// Returns whether the samples of the image are stored as they are, without compression,
+    /// predictor, subsampling or bit packing.
+    #[cfg(feature = "mmap")]
+    pub(super) fn is_uncompressed(&self) -> Result<bool, TiffParserError> {
+        use super::compression::COMPRESSION_NONE;
+
+        let compression = match self.get_value(TAG_COMPRESSION) {
+            Ok(value) => value.short()?,
+            Err(_) => COMPRESSION_NONE,
+        };
+        let predictor = match self.get_value(TAG_PREDICTOR) {
+            Ok(value) => value.short()?,
+            Err(_) => 1,
+        };
+        Ok(compression == COMPRESSION_NONE
+            && predictor == 1
+            && self.bits_per_sample()? % 8 == 0
+            && self.photometric_interpretation()? != PHOTOMETRIC_YCBCR)
+    }
+
+    /// Decodes the image data of this IFD and all of its SubIFDs.
+    pub(super) fn read_data<S: Source>(
         &mut self,
         endianness: Endianness,
-        buf: &[u8],
+        source: &mut S,
+        decompressors: &DecompressorRegistry,
     ) -> Result<(), TiffParserError> {
-        let tile_width = self.get_value(TAG_TILE_WIDTH)?.short()? as usize;
-        let tile_length = self.get_value(TAG_TILE_LENGTH)?.short()? as usize;
-        let tile_offsets = self.get_value(TAG_TILE_OFFSETS)?.longs()?;
-        let tile_byte_counts = self.get_value(TAG_TILE_BYTE_COUNTS)?.longs()?;
+        for sub_ifd in &mut self.sub_ifds {
+            sub_ifd.read_data(endianness, source, decompressors)?;
+        }
+        self.data = self.read_image(endianness, source, decompressors)?;
+        Ok(())
+    }
 
-        let image_width = self.get_value(TAG_IMAGE_WIDTH)?.short()? as usize;
-        let image_length = self.get_value(TAG_IMAGE_LENGTH)?.short()? as usize;
+    /// Returns the layout of the strips or tiles of the image, or `None` if the IFD has no
+    /// image data.
+    pub(super) fn chunk_layout(&self) -> Result<Option<ChunkLayout>, TiffParserError> {
+        let (tiled, chunk_offsets_tag, chunk_byte_counts_tag) =
+            match (self.is_stripped(), self.is_tiled()) {
+                (true, false) => (false, TAG_STRIP_OFFSETS, TAG_STRIP_BYTE_COUNTS),
+                (false, true) => (true, TAG_TILE_OFFSETS, TAG_TILE_BYTE_COUNTS),
+                (true, true) => return Err(TiffParserError::ImageBothTiledAndStripped),
+                // no image data
+                (false, false) => return Ok(None),
+            };
 
-        let bits_per_sample = self.get_value(TAG_BITS_PER_SAMPLE)?.short()? as usize;
-        let samples_per_pixel = self.get_value(TAG_SAMPLES_PER_PIXEL)?.short()? as usize;
-        let bytes_per_pixel = bits_per_sample * samples_per_pixel / 8;
+        let image_width = self.layout_dimension(TAG_IMAGE_WIDTH)?;
+        let image_length = self.layout_dimension(TAG_IMAGE_LENGTH)?;
+        let (chunk_width, chunk_length) = self.chunk_dimensions(tiled)?;
 
-        let compression = self.get_value(TAG_COMPRESSION)?.short()?;
+        let samples_per_pixel = self.samples_per_pixel()? as usize;
+        let (planes, chunk_samples) = match self.planar_configuration()? {
+            PLANAR_CONFIGURATION_PLANAR => (samples_per_pixel, 1),
+            _ => (1, samples_per_pixel),
+        };
+        let chunks_across = image_width.div_ceil(chunk_width);
+        let chunks_per_plane = chunks_across.saturating_mul(image_length.div_ceil(chunk_length));
 
-        let tiles = tile_offsets
-            .iter()
-            .zip(tile_byte_counts.iter())
-            .map(|(offset, count)| {
-                let offset = *offset as usize;
-                let count = *count as usize;
-                &buf[offset..offset + count]
-            })
-            .map(|enc_tile| {
-                create_decompressor(compression).and_then(|mut decompressor| {
-                    decompressor
-                        .decompress(enc_tile, tile_width * tile_length * bits_per_sample / 8)
-                })
-            })
+        let layout = ChunkLayout {
+            image_width,
+            image_length,
+            chunk_width,
+            chunk_length,
+            chunks_across,
+            chunks_per_plane,
+            planes,
+            chunk_samples,
+            tiled,
+            offsets: self.get_value(chunk_offsets_tag)?.as_u64s()?,
+            byte_counts: self.get_value(chunk_byte_counts_tag)?.as_u64s()?,
+        };
+        // every chunk covering the image must be present
+        if chunks_per_plane.saturating_mul(planes) > layout.num_chunks() {
+            return Err(TiffParserError::MissingChunk(layout.num_chunks()));
+        }
+        Ok(Some(layout))
+    }
+
+    /// The width and length of the tiles of the image, or of its strips if `tiled` is false.
+    pub(super) fn chunk_dimensions(&self, tiled: bool) -> Result<(usize, usize), TiffParserError> {
+        if tiled {
+            Ok((
+                self.layout_dimension(TAG_TILE_WIDTH)?,
+                self.layout_dimension(TAG_TILE_LENGTH)?,
+            ))
+        } else {
+            // RowsPerStrip may be omitted, its default value of 2**32 - 1 meaning that the whole
+            // image is a single strip
+            let image_length = self.layout_dimension(TAG_IMAGE_LENGTH)?;
+            let rows_per_strip = if self.has_entry(TAG_ROWS_PER_STRIP) {
+                self.layout_dimension(TAG_ROWS_PER_STRIP)?
+            } else {
+                image_length
+            };
+            Ok((
+                self.layout_dimension(TAG_IMAGE_WIDTH)?,
+                rows_per_strip.min(image_length),
+            ))
+        }
+    }
+
+    /// Fetches the given strip or tile from `source` and decodes it.
+    pub(super) fn read_chunk<S: Source>(
+        &self,
+        endianness: Endianness,
+        source: &mut S,
+        decompressors: &DecompressorRegistry,
+        layout: &ChunkLayout,
+        index: usize,
+    ) -> Result<Vec<u8>, TiffParserError> {
+        if index >= layout.num_chunks() {
+            return Err(TiffParserError::MissingChunk(index));
+        }
+        let (offset, len) = (layout.offsets[index], layout.byte_counts[index]);
+        let enc_chunk = match source.read_bytes(offset as usize, len as usize) {
+            Err(TiffParserError::OutOfBounds(..)) => {
+                return Err(TiffParserError::ChunkOutOfBounds(index, offset, len));
+            }
+            enc_chunk => enc_chunk?,
+        };
+        self.decode_chunk(endianness, decompressors, &enc_chunk, layout, index)
+    }
+
+    /// Fetches and decodes all strips or tiles of the image, or returns `None` if the IFD has no
+    /// image data.
+    pub(super) fn read_image<S: Source>(
+        &self,
+        endianness: Endianness,
+        source: &mut S,
+        decompressors: &DecompressorRegistry,
+    ) -> Result<Option<RasterBuffer>, TiffParserError> {
+        let layout = match self.chunk_layout()? {
+            Some(layout) => layout,
+            None => return Ok(None),
+        };
+        // writers sometimes list more chunks than cover the image, which are never read
+        let chunks = (0..layout.chunks_per_plane * layout.planes)
+            .map(|index| self.read_chunk(endianness, source, decompressors, &layout, index))
             .collect::<Result<Vec<_>, _>>()?;
 
-        self.data = Vec::with_capacity(image_length * image_width);
-        let nrow = (image_width + tile_width - 1) / tile_width;
-        for j in 0..image_length {
-            let tile_row = j / tile_length;
-            let jt = j - tile_row * tile_length;
-            for i in 0..image_width {
-                let tile_col = i / tile_width;
-                let it = i - tile_col * tile_width;
-                let bytes =
-                    &tiles[tile_row * nrow + tile_col][bytes_per_pixel * (jt * tile_width + it)..];
-                let pixel = match bytes_per_pixel {
-                    2 => endianness.read_i16(bytes)? as i32,
-                    4 => endianness.read_i32(bytes)?,
-                    _ => unimplemented!(),
-                };
-                self.data.push(pixel);
+        let bytes = self.window_bytes(
+            &layout,
+            |index| chunks.get(index).map(Vec::as_slice),
+            None,
+            (0, 0, layout.image_width, layout.image_length),
+        )?;
+        Ok(Some(self.raster_buffer(endianness, &bytes)?))
+    }
+
+    /// Assembles the samples of the given band, or of all bands if `band` is `None`, within the
+    /// window `(x_off, y_off, width, height)` out of the decoded chunks returned by `chunk`. The
+    /// samples of each pixel are next to each other, regardless of the planar configuration.
+    pub(super) fn window_bytes<'a, F: Fn(usize) -> Option<&'a [u8]>>(
+        &self,
+        layout: &ChunkLayout,
+        chunk: F,
+        band: Option<usize>,
+        window: (usize, usize, usize, usize),
+    ) -> Result<Vec<u8>, TiffParserError> {
+        let bytes_per_sample = self.bytes_per_sample()?;
+        let pixel_size = layout.chunk_samples * bytes_per_sample;
+        let planes = layout
+            .planes_of(band)?
+            .map(|plane| layout.copy_window(&chunk, plane, window, pixel_size))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(match (layout.is_planar(), band) {
+            (false, None) | (true, Some(_)) => planes.into_iter().next().unwrap_or_default(),
+            // pick the band out of the interleaved samples
+            (false, Some(band)) => planes[0]
+                .chunks_exact(pixel_size)
+                .flat_map(|pixel| &pixel[band * bytes_per_sample..(band + 1) * bytes_per_sample])
+                .copied()
+                .collect(),
+            // interleave the samples of the planes
+            (true, None) => {
+                let num_pixels = window.2 * window.3;
+                let mut bytes = Vec::with_capacity(planes.iter().map(Vec::len).sum());
+                for i in 0..num_pixels {
+                    for plane in &planes {
+                        bytes.extend_from_slice(
+                            &plane[i * bytes_per_sample..(i + 1) * bytes_per_sample],
+                        );
+                    }
+                }
+                bytes
+            }
+        })
+    }
+
+    /// Decompresses the strip or tile with the given index and reverses the predictor applied
+    /// to it.
+    fn decode_chunk(
+        &self,
+        endianness: Endianness,
+        decompressors: &DecompressorRegistry,
+        enc_chunk: &[u8],
+        layout: &ChunkLayout,
+        index: usize,
+    ) -> Result<Vec<u8>, TiffParserError> {
+        let (width, rows) = (layout.chunk_width, layout.rows(index));
+        let samples_per_pixel = layout.chunk_samples;
+        let compression = self.get_value(TAG_COMPRESSION)?.short()?;
+        let predictor = match self.get_value(TAG_PREDICTOR) {
+            Ok(value) => value.short()?,
+            Err(_) => 1,
+        };
+        let bits_per_sample = self.bits_per_sample()? as usize;
+        let bytes_per_sample = bits_per_sample / 8;
+
+        // with FillOrder 2 the bits of every byte are stored in reverse order
+        let reversed_chunk;
+        let enc_chunk = if self.fill_order()? == FILL_ORDER_LSB_FIRST {
+            reversed_chunk = enc_chunk
+                .iter()
+                .map(|byte| byte.reverse_bits())
+                .collect::<Vec<_>>();
+            &reversed_chunk
+        } else {
+            enc_chunk
+        };
+
+        // YCbCr data is converted to RGB by the JPEG decoder, otherwise it has to be done here
+        let ycbcr = if self.photometric_interpretation()? == PHOTOMETRIC_YCBCR
+            && compression != COMPRESSION_JPEG
+        {
+            Some(YCbCrConverter::new(self)?)
+        } else {
+            None
+        };
+        // 1-bit samples are unpacked to a byte each, and YCbCr data to 3 bytes per pixel
+        let decoded_size = width
+            .checked_mul(samples_per_pixel)
+            .and_then(|row_samples| row_samples.checked_mul(bytes_per_sample.max(1)))
+            .and_then(|row_size| row_size.checked_mul(rows));
+        let size = match &ycbcr {
+            Some(ycbcr) => ycbcr.encoded_size(width, rows),
+            // rows of samples smaller than a byte are padded to a byte boundary
+            None => width
+                .checked_mul(samples_per_pixel)
+                .and_then(|row_bits| row_bits.checked_mul(bits_per_sample))
+                .and_then(|row_bits| row_bits.div_ceil(8).checked_mul(rows)),
+        }
+        .filter(|size| *size <= MAX_CHUNK_SIZE)
+        .filter(|_| decoded_size.is_some_and(|decoded_size| decoded_size <= MAX_CHUNK_SIZE))
+        .ok_or(TiffParserError::InvalidChunk(index, "too large"))?;
+
+        let mut decompressor = decompressors.create(compression, endianness, self)?;
+        let mut chunk = decompressor.decompress(enc_chunk, size)?;
+        if chunk.len() < size {
+            return Err(TiffParserError::InvalidChunk(index, "truncated data"));
+        }
+        match ycbcr {
+            Some(ycbcr) => {
+                if bytes_per_sample != 1 || samples_per_pixel != 3 {
+                    return Err(TiffParserError::UnsupportedYCbCr);
+                }
+                chunk = ycbcr.to_rgb(&chunk, width, rows);
             }
+            None => reverse_predictor(
+                predictor,
+                endianness,
+                &mut chunk,
+                width,
+                samples_per_pixel,
+                bytes_per_sample,
+            )?,
+        }
+        if bits_per_sample == 1 {
+            let white_is_zero = self.photometric_interpretation()? == PHOTOMETRIC_WHITE_IS_ZERO;
+            chunk = unpack_bits(&chunk, width * samples_per_pixel, white_is_zero);
         }
+        Ok(chunk)
+    }
 
-        Ok(())
+    pub(super) fn raster_buffer(
+        &self,
+        endianness: Endianness,
+        bytes: &[u8],
+    ) -> Result<RasterBuffer, TiffParserError> {
+        RasterBuffer::from_bytes(
+            endianness,
+            self.sample_format()?,
+            self.decoded_bits_per_sample()?,
+            bytes,
+        )
     }
 
     fn is_stripped(&self) -> bool {
-        self.has_entry(TAG_ROWS_PER_STRIP)
-            && self.has_entry(TAG_STRIP_OFFSETS)
-            && self.has_entry(TAG_STRIP_BYTE_COUNTS)
+        self.has_entry(TAG_STRIP_OFFSETS) && self.has_entry(TAG_STRIP_BYTE_COUNTS)
     }
 
-    fn is_tiled(&self) -> bool {
+    pub(super) fn is_tiled(&self) -> bool {
         self.has_entry(TAG_TILE_WIDTH)
             && self.has_entry(TAG_TILE_LENGTH)
             && self.has_entry(TAG_TILE_OFFSETS)
@@ -206,12 +748,303 @@ impl Ifd {
     }
 }
 
+/// Unpacks rows of 1-bit samples, each padded to a byte boundary, to one byte per sample, with 0
+/// for black and 1 for white.
+fn unpack_bits(chunk: &[u8], row_samples: usize, white_is_zero: bool) -> Vec<u8> {
+    let invert = white_is_zero as u8;
+    chunk
+        .chunks_exact(row_samples.div_ceil(8))
+        .flat_map(|row| (0..row_samples).map(move |i| ((row[i / 8] >> (7 - i % 8)) & 1) ^ invert))
+        .collect()
+}
+
 impl fmt::Debug for Ifd {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Ifd")
             .field("entries", &self.entries)
             .field("sub_ifds", &self.sub_ifds)
-            .field("data", &format!("({} pixels)", self.data.len()))
+            .field("data", &self.data)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::*;
+    use crate::parser::{
+        field::Field,
+        raster_buffer::RasterValue,
+        test_tiff::{write_tiff, TestIfd},
+        TiffFile, TiffReader,
+    };
+
+    fn deflate(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn missing_rows_per_strip_means_a_single_strip() {
+        let ifd = TestIfd::stripped(3, 2, 1)
+            .without(TAG_ROWS_PER_STRIP)
+            .strips(vec![vec![1, 2, 3, 4, 5, 6]]);
+        let tiff = TiffFile::from_bytes(&write_tiff(&[ifd])).unwrap();
+        assert_eq!(
+            tiff.ifds()[0].data(),
+            Some(&RasterBuffer::U8(vec![1, 2, 3, 4, 5, 6]))
+        );
+    }
+
+    #[test]
+    fn short_last_strip() {
+        // 3 rows in strips of 2 rows, with the horizontal predictor
+        let ifd = TestIfd::stripped(2, 3, 2)
+            .short(TAG_COMPRESSION, &[8])
+            .short(TAG_PREDICTOR, &[2]);
+        let ifd = ifd.strips(vec![deflate(&[1, 1, 3, 1]), deflate(&[5, 1])]);
+        let tiff = TiffFile::from_bytes(&write_tiff(&[ifd])).unwrap();
+        assert_eq!(
+            tiff.ifds()[0].data(),
+            Some(&RasterBuffer::U8(vec![1, 2, 3, 4, 5, 6]))
+        );
+    }
+
+    #[test]
+    fn truncated_strip() {
+        let ifd = TestIfd::stripped(2, 2, 2).strips(vec![vec![1, 2, 3]]);
+        assert!(matches!(
+            TiffFile::from_bytes(&write_tiff(&[ifd])),
+            Err(TiffParserError::InvalidChunk(0, _))
+        ));
+    }
+
+    #[test]
+    fn one_bit_samples_count_towards_the_size_limit() {
+        // a single row whose packed size is within the limit, but not once unpacked
+        let ifd = TestIfd::stripped(1 << 31, 1, 1)
+            .short(TAG_BITS_PER_SAMPLE, &[1])
+            .strips(vec![vec![0; 16]]);
+        assert!(matches!(
+            TiffFile::from_bytes(&write_tiff(&[ifd])),
+            Err(TiffParserError::InvalidChunk(0, "too large"))
+        ));
+    }
+
+    #[test]
+    fn deeply_nested_sub_ifds() {
+        let nest = |depth| {
+            (0..depth).fold(TestIfd::stripped(1, 1, 1), |ifd, _| {
+                TestIfd::stripped(1, 1, 1).sub_ifd(ifd)
+            })
+        };
+        let tiff = TiffFile::from_bytes(&write_tiff(&[nest(MAX_SUB_IFD_DEPTH)]));
+        assert!(tiff.is_ok());
+        assert!(matches!(
+            TiffFile::from_bytes(&write_tiff(&[nest(MAX_SUB_IFD_DEPTH + 1)])),
+            Err(TiffParserError::SubIfdsTooDeep(MAX_SUB_IFD_DEPTH))
+        ));
+    }
+
+    #[test]
+    fn planar_bands_are_interleaved() {
+        // 3 bands of 2 by 2 pixels, in strips of 1 row
+        let ifd = TestIfd::stripped(2, 2, 1)
+            .short(TAG_SAMPLES_PER_PIXEL, &[3])
+            .short(TAG_BITS_PER_SAMPLE, &[8, 8, 8])
+            .short(TAG_PLANAR_CONFIGURATION, &[2]);
+        let strips = [[1, 2], [3, 4], [11, 12], [13, 14], [21, 22], [23, 24]];
+        let ifd = ifd.strips(strips.iter().map(|strip| strip.to_vec()).collect());
+        let mut reader = TiffReader::new(Cursor::new(write_tiff(&[ifd]))).unwrap();
+        assert_eq!(
+            reader.read_image(0).unwrap(),
+            Some(RasterBuffer::U8(vec![
+                1, 11, 21, 2, 12, 22, 3, 13, 23, 4, 14, 24
+            ]))
+        );
+        assert_eq!(
+            reader.read_window(0, 1, 0, 1, 2).unwrap(),
+            RasterBuffer::U8(vec![2, 12, 22, 4, 14, 24])
+        );
+        assert_eq!(
+            reader.read_band_window(0, 1, 0, 0, 2, 2).unwrap(),
+            RasterBuffer::U8(vec![11, 12, 13, 14])
+        );
+        assert!(matches!(
+            reader.read_band_window(0, 3, 0, 0, 1, 1),
+            Err(TiffParserError::MissingBand(3))
+        ));
+    }
+
+    /// The sample of the given band of a pixel of the 20 by 18 images of the tiled tests.
+    fn tiled_sample(x: usize, y: usize, band: usize) -> u8 {
+        ((y * 20 + x) % 200 + band * 50) as u8
+    }
+
+    /// Tiles of 16 by 16 pixels covering the 20 by 18 image, padded with 255 beyond its edges,
+    /// with `bands` interleaved samples per pixel and only those of band `band` if given.
+    fn tiles(bands: usize, band: Option<usize>) -> Vec<Vec<u8>> {
+        let tile = |tile_x: usize, tile_y: usize| {
+            let mut tile = vec![];
+            for y in tile_y * 16..tile_y * 16 + 16 {
+                for x in tile_x * 16..tile_x * 16 + 16 {
+                    for b in band.map_or(0..bands, |band| band..band + 1) {
+                        tile.push(if x < 20 && y < 18 {
+                            tiled_sample(x, y, b)
+                        } else {
+                            255
+                        });
+                    }
+                }
+            }
+            tile
+        };
+        [(0, 0), (1, 0), (0, 1), (1, 1)]
+            .into_iter()
+            .map(|(tile_x, tile_y)| tile(tile_x, tile_y))
+            .collect()
+    }
+
+    /// The samples of the given bands within a window of the 20 by 18 image.
+    fn tiled_window(
+        bands: &[usize],
+        x_off: usize,
+        y_off: usize,
+        width: usize,
+        height: usize,
+    ) -> RasterBuffer {
+        RasterBuffer::U8(
+            (y_off..y_off + height)
+                .flat_map(|y| {
+                    (x_off..x_off + width)
+                        .flat_map(move |x| bands.iter().map(move |&band| tiled_sample(x, y, band)))
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn chunky_tiles() {
+        let ifd = TestIfd::stripped(20, 18, 18)
+            .short(TAG_SAMPLES_PER_PIXEL, &[2])
+            .short(TAG_BITS_PER_SAMPLE, &[8, 8])
+            .tiles(16, 16, tiles(2, None));
+        let bytes = write_tiff(&[ifd]);
+        let tiff = TiffFile::from_bytes(&bytes).unwrap();
+        assert!(tiff.ifds()[0].is_tiled());
+        // the parts of the edge tiles beyond the image are cropped
+        assert_eq!(
+            tiff.ifds()[0].data(),
+            Some(&tiled_window(&[0, 1], 0, 0, 20, 18))
+        );
+
+        let mut reader = TiffReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.num_chunks(0).unwrap(), 4);
+        // a window across the four tiles, reaching the bottom-right corner
+        assert_eq!(
+            reader.read_window(0, 10, 12, 10, 6).unwrap(),
+            tiled_window(&[0, 1], 10, 12, 10, 6)
+        );
+        assert_eq!(
+            reader.read_band_window(0, 1, 15, 15, 2, 2).unwrap(),
+            tiled_window(&[1], 15, 15, 2, 2)
+        );
+        // whole tiles are returned, padding included
+        assert_eq!(reader.read_chunk(0, 3).unwrap().len(), 16 * 16 * 2);
+    }
+
+    #[test]
+    fn planar_tiles() {
+        let planes = [tiles(2, Some(0)), tiles(2, Some(1))].concat();
+        let ifd = TestIfd::stripped(20, 18, 18)
+            .short(TAG_SAMPLES_PER_PIXEL, &[2])
+            .short(TAG_BITS_PER_SAMPLE, &[8, 8])
+            .short(TAG_PLANAR_CONFIGURATION, &[2]);
+        let bytes = write_tiff(&[ifd.tiles(16, 16, planes)]);
+        let tiff = TiffFile::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            tiff.ifds()[0].data(),
+            Some(&tiled_window(&[0, 1], 0, 0, 20, 18))
+        );
+
+        let mut reader = TiffReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.num_chunks(0).unwrap(), 8);
+        assert_eq!(
+            reader.read_window(0, 10, 12, 10, 6).unwrap(),
+            tiled_window(&[0, 1], 10, 12, 10, 6)
+        );
+        assert_eq!(
+            reader.read_band_window(0, 1, 3, 14, 17, 4).unwrap(),
+            tiled_window(&[1], 3, 14, 17, 4)
+        );
+    }
+
+    #[test]
+    fn single_bands_of_planar_tiles() {
+        // the tiles of the first band are missing, which doesn't matter when reading the second
+        let planes = [vec![vec![]; 4], tiles(2, Some(1))].concat();
+        let ifd = TestIfd::stripped(20, 18, 18)
+            .short(TAG_SAMPLES_PER_PIXEL, &[2])
+            .short(TAG_BITS_PER_SAMPLE, &[8, 8])
+            .short(TAG_PLANAR_CONFIGURATION, &[2])
+            .tiles(16, 16, planes);
+        let mut reader = TiffReader::new(Cursor::new(write_tiff(&[ifd]))).unwrap();
+        assert_eq!(
+            reader.read_band_window(0, 1, 0, 0, 20, 18).unwrap(),
+            tiled_window(&[1], 0, 0, 20, 18)
+        );
+        assert!(reader.read_band_window(0, 0, 0, 0, 1, 1).is_err());
+        assert!(reader.read_window(0, 0, 0, 1, 1).is_err());
+    }
+
+    #[test]
+    fn dimensions_above_65535() {
+        // ImageWidth and TileWidth are written as LONG
+        let tiff = TiffFile::from_bytes(&write_tiff(&[TestIfd::stripped(70000, 1, 1)
+            .strips(vec![(0..70000).map(|x| (x / 1000) as u8).collect()])]))
+        .unwrap();
+        let ifd = &tiff.ifds()[0];
+        assert_eq!(ifd.image_width(), Some(70000));
+        assert_eq!(
+            ifd.data().and_then(|data| data.get(69999)),
+            Some(RasterValue::U8(69))
+        );
+
+        let ifd =
+            TestIfd::stripped(1, 1, 1).tiles(65536 + 16, 16, vec![vec![1; (65536 + 16) * 16]]);
+        let tiff = TiffFile::from_bytes(&write_tiff(&[ifd])).unwrap();
+        assert_eq!(tiff.ifds()[0].chunk_dimensions(true).unwrap(), (65552, 16));
+        assert_eq!(tiff.ifds()[0].data(), Some(&RasterBuffer::U8(vec![1])));
+    }
+
+    #[test]
+    fn offsets_of_any_integer_type() {
+        for field in [Field::Short, Field::Long, Field::Long8] {
+            let ifd = TestIfd::stripped(2, 2, 1)
+                .strips(vec![vec![1, 2], vec![3, 4]])
+                .offsets_field(field);
+            let tiff = TiffFile::from_bytes(&write_tiff(&[ifd])).unwrap();
+            assert_eq!(
+                tiff.ifds()[0].data(),
+                Some(&RasterBuffer::U8(vec![1, 2, 3, 4])),
+                "strip offsets as {field:?}"
+            );
+
+            let ifd = TestIfd::stripped(20, 18, 18)
+                .short(TAG_SAMPLES_PER_PIXEL, &[2])
+                .short(TAG_BITS_PER_SAMPLE, &[8, 8])
+                .tiles(16, 16, tiles(2, None))
+                .offsets_field(field);
+            let mut reader = TiffReader::new(Cursor::new(write_tiff(&[ifd]))).unwrap();
+            assert_eq!(
+                reader.read_window(0, 10, 12, 10, 6).unwrap(),
+                tiled_window(&[0, 1], 10, 12, 10, 6),
+                "tile offsets as {field:?}"
+            );
+        }
+    }
+}
@@ -3,9 +3,26 @@ mod endianness;
 mod error;
 mod field;
 mod ifd;
-mod tags;
+mod predictor;
+mod raster_buffer;
+mod source;
+pub(crate) mod tags;
+#[cfg(test)]
+pub(crate) mod test_tiff;
 mod tiff_file;
+mod tiff_reader;
 mod value;
+mod variant;
+mod ycbcr;
 
+pub use compression::{Decompressor, DecompressorRegistry};
+pub use endianness::Endianness;
 pub use error::TiffParserError;
-pub use tiff_file::TiffFile;
+pub use ifd::Ifd;
+pub use raster_buffer::{RasterBuffer, RasterValue, Sample};
+pub use source::ByteSource;
+#[cfg(feature = "http")]
+pub use source::HttpSource;
+pub use tiff_file::{TiffFile, TiffOptions};
+pub use tiff_reader::TiffReader;
+pub use value::Value;
@@ -0,0 +1,182 @@
+use std::convert::TryInto;
+
+use super::{endianness::Endianness, TiffParserError};
+
+const PREDICTOR_NONE: u16 = 1;
+const PREDICTOR_HORIZONTAL: u16 = 2;
+const PREDICTOR_FLOATING_POINT: u16 = 3;
+
+macro_rules! accumulate {
+    ($row:expr, $stride:expr, $endianness:expr, $ty:ty) => {{
+        const SIZE: usize = std::mem::size_of::<$ty>();
+        let (from_bytes, to_bytes): (fn([u8; SIZE]) -> $ty, fn($ty) -> [u8; SIZE]) =
+            match $endianness {
+                Endianness::LittleEndian => (<$ty>::from_le_bytes, <$ty>::to_le_bytes),
+                Endianness::BigEndian => (<$ty>::from_be_bytes, <$ty>::to_be_bytes),
+            };
+        let num_samples = $row.len() / SIZE;
+        for i in $stride..num_samples {
+            let prev = from_bytes($row[(i - $stride) * SIZE..][..SIZE].try_into()?);
+            let cur = from_bytes($row[i * SIZE..][..SIZE].try_into()?);
+            $row[i * SIZE..][..SIZE].copy_from_slice(&to_bytes(cur.wrapping_add(prev)));
+        }
+    }};
+}
+
+/// Reverses the differencing applied to the decompressed data of a single strip or tile
+/// consisting of rows of `row_width` pixels.
+pub(super) fn reverse_predictor(
+    predictor: u16,
+    endianness: Endianness,
+    data: &mut [u8],
+    row_width: usize,
+    samples_per_pixel: usize,
+    bytes_per_sample: usize,
+) -> Result<(), TiffParserError> {
+    let row_size = row_width * samples_per_pixel * bytes_per_sample;
+    if row_size == 0 {
+        return Ok(());
+    }
+    match predictor {
+        PREDICTOR_NONE => {}
+        PREDICTOR_HORIZONTAL => {
+            for row in data.chunks_exact_mut(row_size) {
+                match bytes_per_sample {
+                    1 => accumulate!(row, samples_per_pixel, endianness, u8),
+                    2 => accumulate!(row, samples_per_pixel, endianness, u16),
+                    4 => accumulate!(row, samples_per_pixel, endianness, u32),
+                    8 => accumulate!(row, samples_per_pixel, endianness, u64),
+                    _ => return Err(TiffParserError::UnsupportedPredictor(predictor)),
+                }
+            }
+        }
+        PREDICTOR_FLOATING_POINT => {
+            let mut shuffled = vec![0; row_size];
+            for row in data.chunks_exact_mut(row_size) {
+                // the bytes are differenced individually, with a stride of one pixel...
+                for i in samples_per_pixel..row_size {
+                    row[i] = row[i].wrapping_add(row[i - samples_per_pixel]);
+                }
+                // ...after being rearranged so that the most significant bytes of all the
+                // samples come first, followed by the next most significant ones, and so on
+                shuffled.copy_from_slice(row);
+                let num_samples = row_size / bytes_per_sample;
+                for sample in 0..num_samples {
+                    for byte in 0..bytes_per_sample {
+                        let significance = match endianness {
+                            Endianness::LittleEndian => bytes_per_sample - byte - 1,
+                            Endianness::BigEndian => byte,
+                        };
+                        row[sample * bytes_per_sample + byte] =
+                            shuffled[significance * num_samples + sample];
+                    }
+                }
+            }
+        }
+        predictor => return Err(TiffParserError::UnsupportedPredictor(predictor)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENDIANNESSES: [Endianness; 2] = [Endianness::LittleEndian, Endianness::BigEndian];
+
+    fn to_bytes(value: u64, size: usize, endianness: Endianness) -> Vec<u8> {
+        match endianness {
+            Endianness::LittleEndian => value.to_le_bytes()[..size].to_vec(),
+            Endianness::BigEndian => value.to_be_bytes()[8 - size..].to_vec(),
+        }
+    }
+
+    #[test]
+    fn horizontal_predictor_of_every_sample_size() {
+        // 2 rows of 3 pixels of 2 samples, wrapping around in both directions
+        let values = [0, u64::MAX, 1, 5, 3, 2, 7, 0, 9, 1, 8, 4];
+        let (row_width, samples_per_pixel) = (3, 2);
+        let row_samples = row_width * samples_per_pixel;
+        for size in [1, 2, 4, 8] {
+            let mask = u64::MAX >> (64 - 8 * size);
+            for endianness in ENDIANNESSES {
+                let mut data = vec![];
+                let mut expected = vec![];
+                for (i, value) in values.iter().enumerate() {
+                    let diff = match i % row_samples {
+                        col if col < samples_per_pixel => *value,
+                        _ => value.wrapping_sub(values[i - samples_per_pixel]),
+                    };
+                    data.extend(to_bytes(diff & mask, size, endianness));
+                    expected.extend(to_bytes(value & mask, size, endianness));
+                }
+                reverse_predictor(
+                    PREDICTOR_HORIZONTAL,
+                    endianness,
+                    &mut data,
+                    row_width,
+                    samples_per_pixel,
+                    size,
+                )
+                .unwrap();
+                assert_eq!(data, expected, "{size} bytes, {endianness:?}");
+            }
+        }
+    }
+
+    /// Applies the floating-point predictor to a single row of samples given as their
+    /// big-endian bytes.
+    fn encode_floating_point(samples: &[Vec<u8>], samples_per_pixel: usize) -> Vec<u8> {
+        let size = samples[0].len();
+        let mut row = vec![0; samples.len() * size];
+        for (sample, bytes) in samples.iter().enumerate() {
+            for (significance, byte) in bytes.iter().enumerate() {
+                row[significance * samples.len() + sample] = *byte;
+            }
+        }
+        for i in (samples_per_pixel..row.len()).rev() {
+            row[i] = row[i].wrapping_sub(row[i - samples_per_pixel]);
+        }
+        row
+    }
+
+    #[test]
+    fn floating_point_predictor() {
+        let values = [1.5, -2.25, 3.0e10, 0.0, f64::MAX, -1.0e-7];
+        for samples_per_pixel in [1, 2] {
+            for endianness in ENDIANNESSES {
+                let singles = values.map(|value| (value as f32).to_be_bytes().to_vec());
+                let doubles = values.map(|value| value.to_be_bytes().to_vec());
+                for samples in [singles.to_vec(), doubles.to_vec()] {
+                    let size = samples[0].len();
+                    let mut data = encode_floating_point(&samples, samples_per_pixel);
+                    reverse_predictor(
+                        PREDICTOR_FLOATING_POINT,
+                        endianness,
+                        &mut data,
+                        values.len() / samples_per_pixel,
+                        samples_per_pixel,
+                        size,
+                    )
+                    .unwrap();
+                    let expected = samples
+                        .iter()
+                        .flat_map(|bytes| match endianness {
+                            Endianness::LittleEndian => bytes.iter().rev().copied().collect(),
+                            Endianness::BigEndian => bytes.clone(),
+                        })
+                        .collect::<Vec<_>>();
+                    assert_eq!(data, expected, "{size} bytes, {endianness:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_predictor() {
+        assert!(matches!(
+            reverse_predictor(4, Endianness::LittleEndian, &mut [0; 4], 4, 1, 1),
+            Err(TiffParserError::UnsupportedPredictor(4))
+        ));
+    }
+}
@@ -0,0 +1,329 @@
+use std::fmt;
+
+use super::{endianness::Endianness, TiffParserError};
+
+const SAMPLE_FORMAT_UINT: u16 = 1;
+const SAMPLE_FORMAT_INT: u16 = 2;
+const SAMPLE_FORMAT_IEEE_FP: u16 = 3;
+const SAMPLE_FORMAT_UNDEFINED: u16 = 4;
+
+/// Decoded pixel data. Samples are stored row by row, with the samples of each pixel next to
+/// each other.
+#[derive(Clone, PartialEq)]
+pub enum RasterBuffer {
+    U8(Vec<u8>),
+    I8(Vec<i8>),
+    U16(Vec<u16>),
+    I16(Vec<i16>),
+    U32(Vec<u32>),
+    I32(Vec<i32>),
+    U64(Vec<u64>),
+    I64(Vec<i64>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+}
+
+/// A single sample taken from a `RasterBuffer`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RasterValue {
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    U64(u64),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+}
+
+macro_rules! read_samples {
+    ($buf:expr, $size:expr, $read:expr) => {
+        $buf.chunks_exact($size)
+            .map($read)
+            .collect::<Result<Vec<_>, TiffParserError>>()?
+    };
+}
+
+impl RasterBuffer {
+    pub(super) fn from_bytes(
+        endianness: Endianness,
+        sample_format: u16,
+        bits_per_sample: u16,
+        buf: &[u8],
+    ) -> Result<Self, TiffParserError> {
+        let buffer = match (sample_format, bits_per_sample) {
+            (SAMPLE_FORMAT_UINT | SAMPLE_FORMAT_UNDEFINED, 8) => RasterBuffer::U8(buf.to_vec()),
+            (SAMPLE_FORMAT_INT, 8) => RasterBuffer::I8(buf.iter().map(|b| *b as i8).collect()),
+            (SAMPLE_FORMAT_UINT | SAMPLE_FORMAT_UNDEFINED, 16) => {
+                RasterBuffer::U16(read_samples!(buf, 2, |b| endianness.read_u16(b)))
+            }
+            (SAMPLE_FORMAT_INT, 16) => {
+                RasterBuffer::I16(read_samples!(buf, 2, |b| endianness.read_i16(b)))
+            }
+            (SAMPLE_FORMAT_UINT | SAMPLE_FORMAT_UNDEFINED, 32) => {
+                RasterBuffer::U32(read_samples!(buf, 4, |b| endianness.read_u32(b)))
+            }
+            (SAMPLE_FORMAT_INT, 32) => {
+                RasterBuffer::I32(read_samples!(buf, 4, |b| endianness.read_i32(b)))
+            }
+            (SAMPLE_FORMAT_UINT | SAMPLE_FORMAT_UNDEFINED, 64) => {
+                RasterBuffer::U64(read_samples!(buf, 8, |b| endianness.read_u64(b)))
+            }
+            (SAMPLE_FORMAT_INT, 64) => {
+                RasterBuffer::I64(read_samples!(buf, 8, |b| endianness.read_i64(b)))
+            }
+            (SAMPLE_FORMAT_IEEE_FP, 32) => {
+                RasterBuffer::F32(read_samples!(buf, 4, |b| endianness.read_f32(b)))
+            }
+            (SAMPLE_FORMAT_IEEE_FP, 64) => {
+                RasterBuffer::F64(read_samples!(buf, 8, |b| endianness.read_f64(b)))
+            }
+            (sample_format, bits_per_sample) => {
+                return Err(TiffParserError::UnsupportedSampleFormat(
+                    sample_format,
+                    bits_per_sample,
+                ));
+            }
+        };
+        Ok(buffer)
+    }
+
+    /// The number of samples in the buffer.
+    pub fn len(&self) -> usize {
+        match self {
+            RasterBuffer::U8(vals) => vals.len(),
+            RasterBuffer::I8(vals) => vals.len(),
+            RasterBuffer::U16(vals) => vals.len(),
+            RasterBuffer::I16(vals) => vals.len(),
+            RasterBuffer::U32(vals) => vals.len(),
+            RasterBuffer::I32(vals) => vals.len(),
+            RasterBuffer::U64(vals) => vals.len(),
+            RasterBuffer::I64(vals) => vals.len(),
+            RasterBuffer::F32(vals) => vals.len(),
+            RasterBuffer::F64(vals) => vals.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a buffer containing the samples at the given indices, in the same order.
+    pub(crate) fn select<I: Iterator<Item = usize>>(&self, indices: I) -> RasterBuffer {
+        match self {
+            RasterBuffer::U8(vals) => RasterBuffer::U8(indices.map(|i| vals[i]).collect()),
+            RasterBuffer::I8(vals) => RasterBuffer::I8(indices.map(|i| vals[i]).collect()),
+            RasterBuffer::U16(vals) => RasterBuffer::U16(indices.map(|i| vals[i]).collect()),
+            RasterBuffer::I16(vals) => RasterBuffer::I16(indices.map(|i| vals[i]).collect()),
+            RasterBuffer::U32(vals) => RasterBuffer::U32(indices.map(|i| vals[i]).collect()),
+            RasterBuffer::I32(vals) => RasterBuffer::I32(indices.map(|i| vals[i]).collect()),
+            RasterBuffer::U64(vals) => RasterBuffer::U64(indices.map(|i| vals[i]).collect()),
+            RasterBuffer::I64(vals) => RasterBuffer::I64(indices.map(|i| vals[i]).collect()),
+            RasterBuffer::F32(vals) => RasterBuffer::F32(indices.map(|i| vals[i]).collect()),
+            RasterBuffer::F64(vals) => RasterBuffer::F64(indices.map(|i| vals[i]).collect()),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<RasterValue> {
+        match self {
+            RasterBuffer::U8(vals) => vals.get(index).copied().map(RasterValue::U8),
+            RasterBuffer::I8(vals) => vals.get(index).copied().map(RasterValue::I8),
+            RasterBuffer::U16(vals) => vals.get(index).copied().map(RasterValue::U16),
+            RasterBuffer::I16(vals) => vals.get(index).copied().map(RasterValue::I16),
+            RasterBuffer::U32(vals) => vals.get(index).copied().map(RasterValue::U32),
+            RasterBuffer::I32(vals) => vals.get(index).copied().map(RasterValue::I32),
+            RasterBuffer::U64(vals) => vals.get(index).copied().map(RasterValue::U64),
+            RasterBuffer::I64(vals) => vals.get(index).copied().map(RasterValue::I64),
+            RasterBuffer::F32(vals) => vals.get(index).copied().map(RasterValue::F32),
+            RasterBuffer::F64(vals) => vals.get(index).copied().map(RasterValue::F64),
+        }
+    }
+}
+
+/// Parses an integer sample, also accepting integral values written in floating-point notation.
+macro_rules! parse_int {
+    ($string:expr, $type:ty, $variant:ident) => {
+        $string
+            .parse::<$type>()
+            .ok()
+            .or_else(|| {
+                let val = $string.parse::<f64>().ok()?;
+                (val.fract() == 0.0 && val >= <$type>::MIN as f64 && val <= <$type>::MAX as f64)
+                    .then_some(val as $type)
+            })
+            .map(RasterValue::$variant)
+    };
+}
+
+impl RasterValue {
+    /// Parses a sample of the given format from its textual representation, as found in the
+    /// GDAL_NODATA tag. Returns `None` if the string doesn't represent a value of that format.
+    pub(super) fn parse(sample_format: u16, bits_per_sample: u16, string: &str) -> Option<Self> {
+        let string = string.trim();
+        match (sample_format, bits_per_sample) {
+            (SAMPLE_FORMAT_UINT | SAMPLE_FORMAT_UNDEFINED, 8) => parse_int!(string, u8, U8),
+            (SAMPLE_FORMAT_INT, 8) => parse_int!(string, i8, I8),
+            (SAMPLE_FORMAT_UINT | SAMPLE_FORMAT_UNDEFINED, 16) => parse_int!(string, u16, U16),
+            (SAMPLE_FORMAT_INT, 16) => parse_int!(string, i16, I16),
+            (SAMPLE_FORMAT_UINT | SAMPLE_FORMAT_UNDEFINED, 32) => parse_int!(string, u32, U32),
+            (SAMPLE_FORMAT_INT, 32) => parse_int!(string, i32, I32),
+            (SAMPLE_FORMAT_UINT | SAMPLE_FORMAT_UNDEFINED, 64) => parse_int!(string, u64, U64),
+            (SAMPLE_FORMAT_INT, 64) => parse_int!(string, i64, I64),
+            // this also accepts "nan" and "inf" in any case
+            (SAMPLE_FORMAT_IEEE_FP, 32) => string.parse().ok().map(RasterValue::F32),
+            (SAMPLE_FORMAT_IEEE_FP, 64) => string.parse().ok().map(RasterValue::F64),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the value equals `other`, treating NaN values as equal to each other.
+    pub fn matches(&self, other: &RasterValue) -> bool {
+        self == other || (self.as_f64().is_nan() && other.as_f64().is_nan())
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            RasterValue::U8(val) => val as f64,
+            RasterValue::I8(val) => val as f64,
+            RasterValue::U16(val) => val as f64,
+            RasterValue::I16(val) => val as f64,
+            RasterValue::U32(val) => val as f64,
+            RasterValue::I32(val) => val as f64,
+            RasterValue::U64(val) => val as f64,
+            RasterValue::I64(val) => val as f64,
+            RasterValue::F32(val) => val as f64,
+            RasterValue::F64(val) => val,
+        }
+    }
+}
+
+impl fmt::Debug for RasterBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            RasterBuffer::U8(_) => "U8",
+            RasterBuffer::I8(_) => "I8",
+            RasterBuffer::U16(_) => "U16",
+            RasterBuffer::I16(_) => "I16",
+            RasterBuffer::U32(_) => "U32",
+            RasterBuffer::I32(_) => "I32",
+            RasterBuffer::U64(_) => "U64",
+            RasterBuffer::I64(_) => "I64",
+            RasterBuffer::F32(_) => "F32",
+            RasterBuffer::F64(_) => "F64",
+        };
+        f.debug_tuple(name)
+            .field(&format_args!("{} samples", self.len()))
+            .finish()
+    }
+}
+
+/// A primitive type in which the samples of a `RasterBuffer` can be stored.
+pub trait Sample: Copy {
+    /// Extracts the sample from a `RasterValue` holding this type, or returns `None` if it holds
+    /// another type.
+    fn from_value(value: RasterValue) -> Option<Self>;
+}
+
+macro_rules! impl_sample {
+    ($type:ty, $variant:ident) => {
+        impl Sample for $type {
+            fn from_value(value: RasterValue) -> Option<Self> {
+                match value {
+                    RasterValue::$variant(val) => Some(val),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+impl_sample!(u8, U8);
+impl_sample!(i8, I8);
+impl_sample!(u16, U16);
+impl_sample!(i16, I16);
+impl_sample!(u32, U32);
+impl_sample!(i32, I32);
+impl_sample!(u64, U64);
+impl_sample!(i64, I64);
+impl_sample!(f32, F32);
+impl_sample!(f64, F64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_integers() {
+        let parse = RasterValue::parse;
+        assert_eq!(
+            parse(SAMPLE_FORMAT_UINT, 8, "255"),
+            Some(RasterValue::U8(255))
+        );
+        assert_eq!(
+            parse(SAMPLE_FORMAT_UNDEFINED, 8, " 0\n"),
+            Some(RasterValue::U8(0))
+        );
+        assert_eq!(
+            parse(SAMPLE_FORMAT_INT, 16, "-32768"),
+            Some(RasterValue::I16(-32768))
+        );
+        // GDAL may write integral values in floating-point notation
+        assert_eq!(
+            parse(SAMPLE_FORMAT_INT, 32, "-9999.0"),
+            Some(RasterValue::I32(-9999))
+        );
+        assert_eq!(
+            parse(SAMPLE_FORMAT_UINT, 32, "1e3"),
+            Some(RasterValue::U32(1000))
+        );
+        assert_eq!(
+            parse(SAMPLE_FORMAT_UINT, 64, "18446744073709551615"),
+            Some(RasterValue::U64(u64::MAX))
+        );
+        assert_eq!(
+            parse(SAMPLE_FORMAT_INT, 64, "-1"),
+            Some(RasterValue::I64(-1))
+        );
+
+        // values which aren't representable in the type
+        assert_eq!(parse(SAMPLE_FORMAT_UINT, 8, "-9999"), None);
+        assert_eq!(parse(SAMPLE_FORMAT_UINT, 8, "256"), None);
+        assert_eq!(parse(SAMPLE_FORMAT_INT, 8, "1.5"), None);
+        assert_eq!(parse(SAMPLE_FORMAT_INT, 16, "nan"), None);
+        assert_eq!(parse(SAMPLE_FORMAT_UINT, 16, "none"), None);
+        assert_eq!(parse(SAMPLE_FORMAT_UINT, 12, "1"), None);
+    }
+
+    #[test]
+    fn parse_floats() {
+        let parse = RasterValue::parse;
+        assert_eq!(
+            parse(SAMPLE_FORMAT_IEEE_FP, 32, "-3.5"),
+            Some(RasterValue::F32(-3.5))
+        );
+        assert_eq!(
+            parse(SAMPLE_FORMAT_IEEE_FP, 64, "1e-300"),
+            Some(RasterValue::F64(1e-300))
+        );
+        assert_eq!(
+            parse(SAMPLE_FORMAT_IEEE_FP, 64, "-inf"),
+            Some(RasterValue::F64(f64::NEG_INFINITY))
+        );
+        for string in ["nan", "NaN", "-nan"] {
+            assert!(parse(SAMPLE_FORMAT_IEEE_FP, 32, string)
+                .is_some_and(|value| value.as_f64().is_nan()));
+        }
+        assert_eq!(parse(SAMPLE_FORMAT_IEEE_FP, 16, "1"), None);
+    }
+
+    #[test]
+    fn nan_matches_nan() {
+        let nan = RasterValue::F32(f32::NAN);
+        assert!(nan.matches(&RasterValue::F32(-f32::NAN)));
+        assert!(!nan.matches(&RasterValue::F32(0.0)));
+        assert!(RasterValue::U8(3).matches(&RasterValue::U8(3)));
+        assert!(!RasterValue::U8(3).matches(&RasterValue::U16(3)));
+    }
+}
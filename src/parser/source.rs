@@ -0,0 +1,98 @@
+use std::{
+    borrow::Cow,
+    io::{Read, Seek, SeekFrom},
+};
+
+use super::TiffParserError;
+
+#[cfg(feature = "http")]
+mod http;
+
+#[cfg(feature = "http")]
+pub use http::HttpSource;
+
+/// A source of the bytes of a TIFF file which can be read in arbitrary ranges, such as a local
+/// file, a buffer in memory or a file on a remote server.
+///
+/// It is implemented for everything implementing `Read + Seek`.
+pub trait ByteSource {
+    /// Reads the `len` bytes starting at `offset`. Fewer bytes may only be returned if the range
+    /// extends beyond the end of the source.
+    fn read_range(&mut self, offset: u64, len: usize) -> Result<Vec<u8>, TiffParserError>;
+}
+
+impl<R: Read + Seek> ByteSource for R {
+    fn read_range(&mut self, offset: u64, len: usize) -> Result<Vec<u8>, TiffParserError> {
+        self.seek(SeekFrom::Start(offset))?;
+        // `len` may come from a corrupted file, so the buffer only grows as data is read
+        let mut buf = vec![];
+        self.take(len as u64).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// Random access to the bytes of a TIFF file, be it in memory or behind a `ByteSource`.
+pub(super) trait Source {
+    /// Reads `len` bytes starting at `offset`.
+    fn read_bytes(&mut self, offset: usize, len: usize) -> Result<Cow<'_, [u8]>, TiffParserError>;
+}
+
+impl Source for &[u8] {
+    fn read_bytes(&mut self, offset: usize, len: usize) -> Result<Cow<'_, [u8]>, TiffParserError> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.get(offset..end))
+            .map(Cow::Borrowed)
+            .ok_or(TiffParserError::OutOfBounds(offset, len))
+    }
+}
+
+/// A part of a file held in memory, starting at `offset` within the file.
+pub(super) struct Window<'a> {
+    pub(super) offset: usize,
+    pub(super) bytes: &'a [u8],
+}
+
+impl Source for Window<'_> {
+    fn read_bytes(&mut self, offset: usize, len: usize) -> Result<Cow<'_, [u8]>, TiffParserError> {
+        offset
+            .checked_sub(self.offset)
+            .and_then(|start| self.bytes.get(start..start.checked_add(len)?))
+            .map(Cow::Borrowed)
+            .ok_or(TiffParserError::OutOfBounds(offset, len))
+    }
+}
+
+/// The number of bytes fetched from the start of a `ByteSource` when a file is opened. Most
+/// files, and Cloud Optimized GeoTIFFs in particular, store all of their IFDs at the start, so
+/// that they can be parsed without any further requests.
+const HEADER_SIZE: usize = 16 * 1024;
+
+/// A `Source` reading from a `ByteSource`, keeping the start of the file in memory.
+pub(super) struct RangeSource<B> {
+    pub(super) source: B,
+    header: Vec<u8>,
+}
+
+impl<B: ByteSource> RangeSource<B> {
+    pub(super) fn new(mut source: B) -> Result<Self, TiffParserError> {
+        let header = source.read_range(0, HEADER_SIZE)?;
+        Ok(Self { source, header })
+    }
+}
+
+impl<B: ByteSource> Source for RangeSource<B> {
+    fn read_bytes(&mut self, offset: usize, len: usize) -> Result<Cow<'_, [u8]>, TiffParserError> {
+        if offset
+            .checked_add(len)
+            .is_some_and(|end| end <= self.header.len())
+        {
+            return Ok(Cow::Borrowed(&self.header[offset..offset + len]));
+        }
+        let buf = self.source.read_range(offset as u64, len)?;
+        if buf.len() < len {
+            return Err(TiffParserError::OutOfBounds(offset, len));
+        }
+        Ok(Cow::Owned(buf))
+    }
+}
@@ -0,0 +1,127 @@
+use std::io::Read;
+
+use super::{super::TiffParserError, ByteSource};
+
+/// A `ByteSource` fetching ranges of a remote file with HTTP range requests.
+pub struct HttpSource {
+    agent: ureq::Agent,
+    url: String,
+}
+
+impl HttpSource {
+    pub fn new<S: Into<String>>(url: S) -> Self {
+        Self::with_agent(ureq::Agent::new(), url)
+    }
+
+    /// Creates a source sending its requests with the given agent, which allows configuring
+    /// timeouts, proxies and the like.
+    pub fn with_agent<S: Into<String>>(agent: ureq::Agent, url: S) -> Self {
+        Self {
+            agent,
+            url: url.into(),
+        }
+    }
+}
+
+impl ByteSource for HttpSource {
+    fn read_range(&mut self, offset: u64, len: usize) -> Result<Vec<u8>, TiffParserError> {
+        if len == 0 {
+            return Ok(vec![]);
+        }
+        let range = format!("bytes={}-{}", offset, offset.saturating_add(len as u64 - 1));
+        let response = match self.agent.get(&self.url).set("Range", &range).call() {
+            Ok(response) => response,
+            // the range starts beyond the end of the file
+            Err(ureq::Error::Status(416, _)) => return Ok(vec![]),
+            Err(err) => return Err(TiffParserError::Http(Box::new(err))),
+        };
+        // a server not supporting range requests responds with the whole file
+        let skip = if response.status() == 206 { 0 } else { offset };
+        let mut reader = response.into_reader();
+        std::io::copy(&mut reader.by_ref().take(skip), &mut std::io::sink())?;
+        let mut buf = vec![];
+        reader.take(len as u64).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    const FILE: &[u8] = b"0123456789";
+
+    /// Starts a server for `FILE` on a local port, honoring range requests only if
+    /// `supports_ranges` is true, and returns its URL.
+    fn serve(supports_ranges: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file.tif", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut range = None;
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                        let (start, end) = value.split_once('-').unwrap();
+                        range = Some((
+                            start.parse::<usize>().unwrap(),
+                            end.parse::<usize>().unwrap(),
+                        ));
+                    }
+                }
+                let (status, content_range, body) = match range {
+                    Some((start, _)) if supports_ranges && start >= FILE.len() => {
+                        ("416 Range Not Satisfiable", String::new(), &[][..])
+                    }
+                    Some((start, end)) if supports_ranges => {
+                        let end = end.min(FILE.len() - 1);
+                        let content_range =
+                            format!("Content-Range: bytes {start}-{end}/{}\r\n", FILE.len());
+                        ("206 Partial Content", content_range, &FILE[start..=end])
+                    }
+                    _ => ("200 OK", String::new(), FILE),
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\n{content_range}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(body).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn partial_content() {
+        let mut source = HttpSource::new(serve(true));
+        assert_eq!(source.read_range(2, 3).unwrap(), b"234");
+        assert_eq!(source.read_range(8, 5).unwrap(), b"89");
+        assert_eq!(source.read_range(0, 0).unwrap(), b"");
+    }
+
+    #[test]
+    fn server_ignoring_ranges() {
+        let mut source = HttpSource::new(serve(false));
+        assert_eq!(source.read_range(2, 3).unwrap(), b"234");
+        assert_eq!(source.read_range(8, 5).unwrap(), b"89");
+        assert_eq!(source.read_range(20, 5).unwrap(), b"");
+    }
+
+    #[test]
+    fn range_not_satisfiable() {
+        let mut source = HttpSource::new(serve(true));
+        assert_eq!(source.read_range(10, 4).unwrap(), b"");
+    }
+}
@@ -0,0 +1,254 @@
+//! A minimal little-endian TIFF and BigTIFF writer for building the files used by the tests.
+
+use super::{
+    field::Field,
+    tags::{
+        TAG_BITS_PER_SAMPLE, TAG_COMPRESSION, TAG_IMAGE_LENGTH, TAG_IMAGE_WIDTH,
+        TAG_PHOTOMETRIC_INTERPRETATION, TAG_ROWS_PER_STRIP, TAG_SAMPLES_PER_PIXEL,
+        TAG_STRIP_BYTE_COUNTS, TAG_STRIP_OFFSETS, TAG_SUB_IFDS, TAG_TILE_BYTE_COUNTS,
+        TAG_TILE_LENGTH, TAG_TILE_OFFSETS, TAG_TILE_WIDTH,
+    },
+    variant::TiffVariant,
+};
+
+const FIELD_ASCII: u16 = 2;
+const FIELD_SHORT: u16 = 3;
+const FIELD_LONG: u16 = 4;
+const FIELD_UNDEFINED: u16 = 7;
+const FIELD_DOUBLE: u16 = 12;
+const FIELD_IFD: u16 = 13;
+const FIELD_LONG8: u16 = 16;
+const FIELD_IFD8: u16 = 18;
+
+/// An IFD to be written, along with its strips or tiles and its SubIFDs.
+#[derive(Default)]
+pub(crate) struct TestIfd {
+    entries: Vec<(u16, u16, u64, Vec<u8>)>,
+    chunks: Vec<Vec<u8>>,
+    tiled: bool,
+    /// The field type of the offsets and byte counts of the chunks, if not the default one.
+    offsets_field: Option<Field>,
+    sub_ifds: Vec<TestIfd>,
+}
+
+impl TestIfd {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// An image of the given dimensions with 8 bit samples, stored as strips of `rows_per_strip`
+    /// rows.
+    pub(crate) fn stripped(width: u32, length: u32, rows_per_strip: u32) -> Self {
+        Self::new()
+            .long(TAG_IMAGE_WIDTH, &[width])
+            .long(TAG_IMAGE_LENGTH, &[length])
+            .short(TAG_BITS_PER_SAMPLE, &[8])
+            .short(TAG_COMPRESSION, &[1])
+            .short(TAG_PHOTOMETRIC_INTERPRETATION, &[1])
+            .short(TAG_SAMPLES_PER_PIXEL, &[1])
+            .long(TAG_ROWS_PER_STRIP, &[rows_per_strip])
+    }
+
+    pub(crate) fn ascii(self, tag: u16, value: &str) -> Self {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(0);
+        self.entry(tag, FIELD_ASCII, bytes.len(), bytes)
+    }
+
+    pub(crate) fn undefined(self, tag: u16, bytes: &[u8]) -> Self {
+        self.entry(tag, FIELD_UNDEFINED, bytes.len(), bytes.to_vec())
+    }
+
+    pub(crate) fn short(self, tag: u16, values: &[u16]) -> Self {
+        let bytes = values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        self.entry(tag, FIELD_SHORT, values.len(), bytes)
+    }
+
+    pub(crate) fn long(self, tag: u16, values: &[u32]) -> Self {
+        let bytes = values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        self.entry(tag, FIELD_LONG, values.len(), bytes)
+    }
+
+    pub(crate) fn double(self, tag: u16, values: &[f64]) -> Self {
+        let bytes = values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        self.entry(tag, FIELD_DOUBLE, values.len(), bytes)
+    }
+
+    /// Removes the entry with the given tag.
+    pub(crate) fn without(mut self, tag: u16) -> Self {
+        self.entries.retain(|entry| entry.0 != tag);
+        self
+    }
+
+    pub(crate) fn strips(mut self, chunks: Vec<Vec<u8>>) -> Self {
+        self.chunks = chunks;
+        self.tiled = false;
+        self
+    }
+
+    /// Stores the image as tiles of `tile_width` by `tile_length` pixels instead of strips.
+    pub(crate) fn tiles(self, tile_width: u32, tile_length: u32, chunks: Vec<Vec<u8>>) -> Self {
+        let mut ifd = self
+            .without(TAG_ROWS_PER_STRIP)
+            .long(TAG_TILE_WIDTH, &[tile_width])
+            .long(TAG_TILE_LENGTH, &[tile_length]);
+        ifd.chunks = chunks;
+        ifd.tiled = true;
+        ifd
+    }
+
+    /// Writes the offsets and byte counts of the strips or tiles as SHORT, LONG or LONG8
+    /// instead of the usual type of the variant of the file.
+    pub(crate) fn offsets_field(mut self, field: Field) -> Self {
+        self.offsets_field = Some(field);
+        self
+    }
+
+    pub(crate) fn sub_ifd(mut self, ifd: TestIfd) -> Self {
+        self.sub_ifds.push(ifd);
+        self
+    }
+
+    fn entry(mut self, tag: u16, field: u16, count: usize, bytes: Vec<u8>) -> Self {
+        self.entries.retain(|entry| entry.0 != tag);
+        self.entries.push((tag, field, count as u64, bytes));
+        self
+    }
+
+    /// Writes the chunks, the SubIFDs and the IFD itself to `out`, returning the offset of the
+    /// IFD and that of its next IFD offset. Offsets are written as LONG in classic TIFF files,
+    /// and as LONG8 in BigTIFF files.
+    fn write(&self, variant: TiffVariant, out: &mut Vec<u8>) -> (u64, usize) {
+        let (offset_field, ifd_field) = match variant {
+            TiffVariant::Classic => (FIELD_LONG, FIELD_IFD),
+            TiffVariant::Big => (FIELD_LONG8, FIELD_IFD8),
+        };
+        let int_bytes = |values: &[u64], size: usize| -> Vec<u8> {
+            values
+                .iter()
+                .flat_map(|value| value.to_le_bytes()[..size].to_vec())
+                .collect()
+        };
+        let offset_bytes = |offsets: &[u64]| int_bytes(offsets, variant.offset_size());
+        let (chunks_field, chunks_size) = match self.offsets_field {
+            Some(Field::Short) => (FIELD_SHORT, 2),
+            Some(Field::Long) => (FIELD_LONG, 4),
+            Some(Field::Long8) => (FIELD_LONG8, 8),
+            Some(field) => panic!("chunk offsets can't be written as {field:?}"),
+            None => (offset_field, variant.offset_size()),
+        };
+        let mut entries = self.entries.clone();
+        let (offsets_tag, byte_counts_tag) = if self.tiled {
+            (TAG_TILE_OFFSETS, TAG_TILE_BYTE_COUNTS)
+        } else {
+            (TAG_STRIP_OFFSETS, TAG_STRIP_BYTE_COUNTS)
+        };
+        if !self.chunks.is_empty() {
+            let mut offsets = vec![];
+            for chunk in &self.chunks {
+                offsets.push(out.len() as u64);
+                out.extend_from_slice(chunk);
+            }
+            let counts = self
+                .chunks
+                .iter()
+                .map(|chunk| chunk.len() as u64)
+                .collect::<Vec<_>>();
+            let count = offsets.len() as u64;
+            entries.push((
+                offsets_tag,
+                chunks_field,
+                count,
+                int_bytes(&offsets, chunks_size),
+            ));
+            entries.push((
+                byte_counts_tag,
+                chunks_field,
+                count,
+                int_bytes(&counts, chunks_size),
+            ));
+        }
+        // SubIFDs are chained through their next IFD offsets
+        let mut first_sub_ifd = 0;
+        let mut previous_next: Option<usize> = None;
+        for sub_ifd in &self.sub_ifds {
+            let (offset, next) = sub_ifd.write(variant, out);
+            match previous_next {
+                Some(previous_next) => out[previous_next..][..variant.offset_size()]
+                    .copy_from_slice(&offset_bytes(&[offset])),
+                None => first_sub_ifd = offset,
+            }
+            previous_next = Some(next);
+        }
+        if !self.sub_ifds.is_empty() {
+            entries.push((TAG_SUB_IFDS, ifd_field, 1, offset_bytes(&[first_sub_ifd])));
+        }
+        entries.sort_by_key(|entry| entry.0);
+
+        let mut values = vec![];
+        for (_, _, _, bytes) in &entries {
+            if bytes.len() > variant.offset_size() {
+                out.resize(out.len() + out.len() % 2, 0);
+                values.push(out.len() as u64);
+                out.extend_from_slice(bytes);
+            } else {
+                values.push(0);
+            }
+        }
+        out.resize(out.len() + out.len() % 2, 0);
+        let start = out.len() as u64;
+        match variant {
+            TiffVariant::Classic => out.extend_from_slice(&(entries.len() as u16).to_le_bytes()),
+            TiffVariant::Big => out.extend_from_slice(&(entries.len() as u64).to_le_bytes()),
+        }
+        for ((tag, field, count, bytes), offset) in entries.iter().zip(values) {
+            out.extend_from_slice(&tag.to_le_bytes());
+            out.extend_from_slice(&field.to_le_bytes());
+            out.extend_from_slice(&offset_bytes(&[*count]));
+            if bytes.len() > variant.offset_size() {
+                out.extend_from_slice(&offset_bytes(&[offset]));
+            } else {
+                let mut inline = bytes.clone();
+                inline.resize(variant.offset_size(), 0);
+                out.extend_from_slice(&inline);
+            }
+        }
+        let next = out.len();
+        out.resize(out.len() + variant.offset_size(), 0);
+        (start, next)
+    }
+}
+
+/// Writes a classic TIFF file made of the given chain of IFDs.
+pub(crate) fn write_tiff(ifds: &[TestIfd]) -> Vec<u8> {
+    write(TiffVariant::Classic, ifds)
+}
+
+/// Writes a BigTIFF file made of the given chain of IFDs.
+pub(crate) fn write_bigtiff(ifds: &[TestIfd]) -> Vec<u8> {
+    write(TiffVariant::Big, ifds)
+}
+
+fn write(variant: TiffVariant, ifds: &[TestIfd]) -> Vec<u8> {
+    let (mut out, mut previous_next) = match variant {
+        TiffVariant::Classic => (b"II\x2a\x00\x00\x00\x00\x00".to_vec(), 4),
+        TiffVariant::Big => (b"II\x2b\x00\x08\x00\x00\x00".to_vec(), 8),
+    };
+    out.resize(previous_next + variant.offset_size(), 0);
+    for ifd in ifds {
+        let (offset, next) = ifd.write(variant, &mut out);
+        out[previous_next..][..variant.offset_size()]
+            .copy_from_slice(&offset.to_le_bytes()[..variant.offset_size()]);
+        previous_next = next;
+    }
+    out
+}
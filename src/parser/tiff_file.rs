@@ -1,41 +1,421 @@
-use std::{fs::File, io::Read, path::Path};
+use std::{collections::HashSet, fs::File, io::Read, path::Path};
 
-use super::{endianness::Endianness, ifd::Ifd, TiffParserError};
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+
+use super::{
+    compression::DecompressorRegistry, endianness::Endianness, ifd::Ifd, source::Source,
+    variant::TiffVariant, TiffParserError,
+};
+#[cfg(feature = "mmap")]
+use super::{ifd::ChunkLayout, raster_buffer::RasterValue};
+
+/// Options controlling how a TIFF file is parsed.
+#[derive(Clone)]
+pub struct TiffOptions {
+    /// The decompressors available for decoding image data.
+    pub decompressors: DecompressorRegistry,
+    /// The maximum number of decoded strips or tiles kept in memory by readers decoding them on
+    /// demand.
+    pub chunk_cache_capacity: usize,
+}
+
+impl Default for TiffOptions {
+    fn default() -> Self {
+        Self {
+            decompressors: DecompressorRegistry::new(),
+            chunk_cache_capacity: 64,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct TiffFile {
-    pub(crate) ifds: Vec<Ifd>,
+    endianness: Endianness,
+    ifds: Vec<Ifd>,
+    /// The mapped file, whose image data is accessed in place instead of being decoded.
+    #[cfg(feature = "mmap")]
+    mmap: Option<Mmap>,
+    /// The strips or tiles of each IFD of the mapped file.
+    #[cfg(feature = "mmap")]
+    layouts: Vec<Option<ChunkLayout>>,
 }
 
 impl TiffFile {
     pub fn from_file<P: AsRef<Path>>(name: P) -> Result<Self, TiffParserError> {
+        Self::from_file_with_options(name, &TiffOptions::default())
+    }
+
+    pub fn from_file_with_options<P: AsRef<Path>>(
+        name: P,
+        options: &TiffOptions,
+    ) -> Result<Self, TiffParserError> {
         let mut file = File::open(name)?;
         let mut buffer = vec![];
         file.read_to_end(&mut buffer)?;
 
-        Self::from_bytes(&buffer)
+        Self::from_bytes_with_options(&buffer, options)
     }
 
     pub fn from_bytes(buf: &[u8]) -> Result<TiffFile, TiffParserError> {
-        let endianness = match &buf[0..2] {
-            b"II" => Endianness::LittleEndian,
-            b"MM" => Endianness::BigEndian,
-            marker => {
-                return Err(TiffParserError::UnknownEndiannessMarker(marker.to_vec()));
-            }
+        Self::from_bytes_with_options(buf, &TiffOptions::default())
+    }
+
+    pub fn from_bytes_with_options(
+        buf: &[u8],
+        options: &TiffOptions,
+    ) -> Result<TiffFile, TiffParserError> {
+        let mut source = buf;
+        let (endianness, mut ifds) = read_ifds(&mut source)?;
+        for ifd in &mut ifds {
+            ifd.read_data(endianness, &mut source, &options.decompressors)?;
+        }
+
+        let tiff = TiffFile {
+            endianness,
+            ifds,
+            #[cfg(feature = "mmap")]
+            mmap: None,
+            #[cfg(feature = "mmap")]
+            layouts: vec![],
         };
 
-        let mut next_ifd_offset = endianness.read_u32(&buf[4..])? as usize;
-        let mut ifds = vec![];
+        Ok(tiff)
+    }
+
+    /// Maps the file into memory and reads its IFDs, without decoding any image data. The
+    /// strips and tiles can then be accessed in place with `chunk_bytes` and `get_sample`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this process or any other, for as long as
+    /// the returned `TiffFile` exists. The slices borrowed from the mapping would otherwise change
+    /// while they are borrowed, which is undefined behavior, and reading the pages missing from a
+    /// truncated file crashes the process.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn from_mmap<P: AsRef<Path>>(name: P) -> Result<Self, TiffParserError> {
+        let file = File::open(name)?;
+        // SAFETY: the caller guarantees that the file isn't modified while it is mapped
+        let mmap = unsafe { Mmap::map(&file)? };
+        let (endianness, ifds) = read_ifds(&mut &mmap[..])?;
+        let layouts = ifds
+            .iter()
+            .map(Ifd::chunk_layout)
+            .collect::<Result<_, _>>()?;
+        Ok(TiffFile {
+            endianness,
+            ifds,
+            mmap: Some(mmap),
+            layouts,
+        })
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    pub fn ifds(&self) -> &[Ifd] {
+        &self.ifds
+    }
 
-        while next_ifd_offset != 0 {
-            let (ifd, offset) = Ifd::read(endianness, buf, next_ifd_offset)?;
-            ifds.push(ifd);
-            next_ifd_offset = offset;
+    /// Returns the bytes of the given strip or tile of the image in the given IFD, as they are
+    /// stored in the file, without copying them. For uncompressed images these are the samples
+    /// of the chunk in the byte order of the file.
+    ///
+    /// Only available for files opened with `from_mmap`.
+    #[cfg(feature = "mmap")]
+    pub fn chunk_bytes(&self, ifd: usize, index: usize) -> Result<&[u8], TiffParserError> {
+        let mmap = self.mmap.as_ref().ok_or(TiffParserError::NotMapped)?;
+        let layout = self
+            .layouts
+            .get(ifd)
+            .ok_or(TiffParserError::MissingIfd(ifd))?
+            .as_ref()
+            .ok_or(TiffParserError::MissingChunk(index))?;
+        if index >= layout.num_chunks() {
+            return Err(TiffParserError::MissingChunk(index));
         }
+        let (offset, len) = (layout.offsets[index], layout.byte_counts[index]);
+        (offset as usize)
+            .checked_add(len as usize)
+            .and_then(|end| mmap.get(offset as usize..end))
+            .ok_or(TiffParserError::ChunkOutOfBounds(index, offset, len))
+    }
 
-        let tiff = TiffFile { ifds };
+    /// Returns the value of the given band of the pixel in column `x` and row `y` of the
+    /// uncompressed image in the given IFD, read directly from the mapped file, or `None` if the
+    /// pixel doesn't exist.
+    ///
+    /// Only available for files opened with `from_mmap`.
+    #[cfg(feature = "mmap")]
+    pub fn get_sample(
+        &self,
+        ifd: usize,
+        x: usize,
+        y: usize,
+        band: usize,
+    ) -> Result<Option<RasterValue>, TiffParserError> {
+        if self.mmap.is_none() {
+            return Err(TiffParserError::NotMapped);
+        }
+        let ifd_index = ifd;
+        let ifd = self.ifds.get(ifd).ok_or(TiffParserError::MissingIfd(ifd))?;
+        let layout = match &self.layouts[ifd_index] {
+            Some(layout) => layout,
+            None => return Ok(None),
+        };
+        if x >= layout.image_width || y >= layout.image_length || band >= layout.samples_per_pixel()
+        {
+            return Ok(None);
+        }
+        if !ifd.is_uncompressed()? {
+            return Err(TiffParserError::CompressedImage);
+        }
 
-        Ok(tiff)
+        let chunk_row = y / layout.chunk_length;
+        let chunk_col = x / layout.chunk_width;
+        let (yc, xc) = (
+            y - chunk_row * layout.chunk_length,
+            x - chunk_col * layout.chunk_width,
+        );
+        // with separate planes each band is in its own chunks
+        let (plane, band) = if layout.is_planar() {
+            (band, 0)
+        } else {
+            (0, band)
+        };
+        let index = layout.index(plane, chunk_row, chunk_col);
+        let chunk = self.chunk_bytes(ifd_index, index)?;
+        let bits_per_sample = ifd.bits_per_sample()?;
+        let bytes_per_sample = bits_per_sample as usize / 8;
+        let start =
+            ((yc * layout.chunk_width + xc) * layout.chunk_samples + band) * bytes_per_sample;
+        let bytes = chunk
+            .get(start..start + bytes_per_sample)
+            .ok_or(TiffParserError::InvalidChunk(index, "truncated data"))?;
+        Ok(ifd.raster_buffer(self.endianness, bytes)?.get(0))
+    }
+}
+
+/// Reads the header of a TIFF file and the entries of all of its IFDs, without decoding any
+/// image data.
+pub(super) fn read_ifds<S: Source>(
+    source: &mut S,
+) -> Result<(Endianness, Vec<Ifd>), TiffParserError> {
+    let header = source.read_bytes(0, 8)?;
+    let endianness = match &header[0..2] {
+        b"II" => Endianness::LittleEndian,
+        b"MM" => Endianness::BigEndian,
+        marker => {
+            return Err(TiffParserError::UnknownEndiannessMarker(marker.to_vec()));
+        }
+    };
+
+    let variant = match endianness.read_u16(&header[2..])? {
+        42 => TiffVariant::Classic,
+        43 => {
+            let offset_size = endianness.read_u16(&header[4..])?;
+            if offset_size != 8 {
+                return Err(TiffParserError::UnsupportedOffsetSize(offset_size));
+            }
+            TiffVariant::Big
+        }
+        version => {
+            return Err(TiffParserError::UnknownVersion(version));
+        }
+    };
+
+    let first_ifd_offset_start = match variant {
+        TiffVariant::Classic => 4,
+        TiffVariant::Big => 8,
+    };
+    let mut next_ifd_offset = variant.read_offset(
+        endianness,
+        &source.read_bytes(first_ifd_offset_start, variant.offset_size())?,
+    )?;
+    let mut ifds = vec![];
+    let mut visited = HashSet::new();
+
+    while next_ifd_offset != 0 {
+        let (ifd, offset) = Ifd::read(
+            endianness,
+            variant,
+            source,
+            next_ifd_offset,
+            &mut visited,
+            0,
+        )?;
+        ifds.push(ifd);
+        next_ifd_offset = offset;
+    }
+
+    Ok((endianness, ifds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "mmap")]
+    use crate::parser::tags::{
+        TAG_BITS_PER_SAMPLE, TAG_COMPRESSION, TAG_PLANAR_CONFIGURATION, TAG_SAMPLES_PER_PIXEL,
+    };
+    use crate::parser::{
+        raster_buffer::RasterBuffer,
+        test_tiff::{write_bigtiff, write_tiff, TestIfd},
+    };
+
+    #[test]
+    fn bigtiff() {
+        // LONG8 strip offsets and byte counts, and an IFD8 SubIFD
+        let ifd = TestIfd::stripped(2, 2, 1)
+            .strips(vec![vec![1, 2], vec![3, 4]])
+            .sub_ifd(TestIfd::stripped(1, 1, 1).strips(vec![vec![5]]));
+        let tiff = TiffFile::from_bytes(&write_bigtiff(&[ifd])).unwrap();
+        assert_eq!(
+            tiff.ifds()[0].data(),
+            Some(&RasterBuffer::U8(vec![1, 2, 3, 4]))
+        );
+        assert_eq!(
+            tiff.ifds()[0].sub_ifds()[0].data(),
+            Some(&RasterBuffer::U8(vec![5]))
+        );
+    }
+
+    #[test]
+    fn sub_ifds_of_type_ifd() {
+        // libtiff writes SubIFDs as IFD rather than LONG in classic TIFF files
+        let ifd = TestIfd::stripped(1, 1, 1)
+            .strips(vec![vec![1]])
+            .sub_ifd(TestIfd::stripped(1, 1, 1).strips(vec![vec![2]]));
+        let tiff = TiffFile::from_bytes(&write_tiff(&[ifd])).unwrap();
+        assert_eq!(
+            tiff.ifds()[0].sub_ifds()[0].data(),
+            Some(&RasterBuffer::U8(vec![2]))
+        );
+    }
+
+    /// Writes the IFDs to a file in the temporary directory and maps it.
+    #[cfg(feature = "mmap")]
+    fn mapped(name: &str, ifds: &[TestIfd]) -> TiffFile {
+        let path = std::env::temp_dir().join(format!("{}-{name}.tif", std::process::id()));
+        std::fs::write(&path, write_tiff(ifds)).unwrap();
+        // SAFETY: the file is only written by this test, before it is mapped
+        let tiff = unsafe { TiffFile::from_mmap(&path) }.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        tiff
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mapped_strips() {
+        // 3 by 3 pixels in strips of 2 rows, the last one short
+        let ifd = TestIfd::stripped(3, 3, 2).strips(vec![vec![1, 2, 3, 4, 5, 6], vec![7, 8, 9]]);
+        let tiff = mapped("strips", &[ifd]);
+        assert_eq!(tiff.ifds().len(), 1);
+        // the image data isn't decoded
+        assert_eq!(tiff.ifds()[0].data(), None);
+        assert_eq!(tiff.chunk_bytes(0, 0).unwrap(), &[1, 2, 3, 4, 5, 6]);
+        assert_eq!(tiff.chunk_bytes(0, 1).unwrap(), &[7, 8, 9]);
+        assert!(matches!(
+            tiff.chunk_bytes(0, 2),
+            Err(TiffParserError::MissingChunk(2))
+        ));
+        assert!(matches!(
+            tiff.chunk_bytes(1, 0),
+            Err(TiffParserError::MissingIfd(1))
+        ));
+
+        assert_eq!(
+            tiff.get_sample(0, 1, 0, 0).unwrap(),
+            Some(RasterValue::U8(2))
+        );
+        assert_eq!(
+            tiff.get_sample(0, 2, 1, 0).unwrap(),
+            Some(RasterValue::U8(6))
+        );
+        assert_eq!(
+            tiff.get_sample(0, 0, 2, 0).unwrap(),
+            Some(RasterValue::U8(7))
+        );
+        assert_eq!(tiff.get_sample(0, 3, 0, 0).unwrap(), None);
+        assert_eq!(tiff.get_sample(0, 0, 3, 0).unwrap(), None);
+        assert_eq!(tiff.get_sample(0, 0, 0, 1).unwrap(), None);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mapped_bands() {
+        // 2 by 2 pixels with 2 bands of 16 bits, interleaved and in separate planes
+        let chunky = TestIfd::stripped(2, 2, 1)
+            .short(TAG_SAMPLES_PER_PIXEL, &[2])
+            .short(TAG_BITS_PER_SAMPLE, &[16, 16])
+            .strips(vec![
+                [1u16, 11, 2, 12]
+                    .iter()
+                    .flat_map(|v| v.to_le_bytes())
+                    .collect(),
+                [3u16, 13, 4, 14]
+                    .iter()
+                    .flat_map(|v| v.to_le_bytes())
+                    .collect(),
+            ]);
+        let planar = TestIfd::stripped(2, 2, 1)
+            .short(TAG_SAMPLES_PER_PIXEL, &[2])
+            .short(TAG_BITS_PER_SAMPLE, &[16, 16])
+            .short(TAG_PLANAR_CONFIGURATION, &[2])
+            .strips(
+                [[1u16, 2], [3, 4], [11, 12], [13, 14]]
+                    .iter()
+                    .map(|strip| strip.iter().flat_map(|v| v.to_le_bytes()).collect())
+                    .collect(),
+            );
+        let tiff = mapped("bands", &[chunky, planar]);
+        for ifd in 0..2 {
+            assert_eq!(
+                tiff.get_sample(ifd, 1, 0, 0).unwrap(),
+                Some(RasterValue::U16(2))
+            );
+            assert_eq!(
+                tiff.get_sample(ifd, 0, 1, 1).unwrap(),
+                Some(RasterValue::U16(13))
+            );
+            assert_eq!(
+                tiff.get_sample(ifd, 1, 1, 1).unwrap(),
+                Some(RasterValue::U16(14))
+            );
+            assert_eq!(tiff.get_sample(ifd, 0, 0, 2).unwrap(), None);
+        }
+        // the planes of the second band follow those of the first
+        assert_eq!(tiff.chunk_bytes(1, 2).unwrap(), &[11, 0, 12, 0]);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn samples_of_compressed_images_are_not_read_in_place() {
+        let ifd = TestIfd::stripped(1, 1, 1)
+            .short(TAG_COMPRESSION, &[8])
+            .strips(vec![vec![1, 2, 3]]);
+        let tiff = mapped("compressed", &[ifd]);
+        // the compressed bytes are still available
+        assert_eq!(tiff.chunk_bytes(0, 0).unwrap(), &[1, 2, 3]);
+        assert!(matches!(
+            tiff.get_sample(0, 0, 0, 0),
+            Err(TiffParserError::CompressedImage)
+        ));
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn files_read_into_memory_are_not_mapped() {
+        let ifd = TestIfd::stripped(1, 1, 1).strips(vec![vec![1]]);
+        let tiff = TiffFile::from_bytes(&write_tiff(&[ifd])).unwrap();
+        assert!(matches!(
+            tiff.chunk_bytes(0, 0),
+            Err(TiffParserError::NotMapped)
+        ));
+        assert!(matches!(
+            tiff.get_sample(0, 0, 0, 0),
+            Err(TiffParserError::NotMapped)
+        ));
     }
 }
@@ -0,0 +1,417 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    fs::File,
+    io::BufReader,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use super::{
+    endianness::Endianness,
+    ifd::{ChunkLayout, Ifd},
+    raster_buffer::RasterBuffer,
+    source::{ByteSource, RangeSource, Source, Window},
+    tiff_file::{read_ifds, TiffOptions},
+    TiffParserError,
+};
+
+/// Strips or tiles separated by at most this many bytes are fetched with a single read, which
+/// is cheaper than a separate request for each of them when reading from a remote server.
+const MAX_RANGE_GAP: u64 = 16 * 1024;
+
+/// A TIFF file read on demand from a `ByteSource`, such as a local file or a Cloud Optimized
+/// GeoTIFF on a remote server.
+///
+/// Only the entries of the IFDs are read when the reader is created, the strips and tiles of
+/// the images are fetched and decoded whenever they are requested, with the ranges of adjacent
+/// ones being coalesced. The most recently decoded strips and tiles are cached for windowed
+/// reads.
+///
+/// Images are addressed by the index of their IFD. The top-level IFDs come first, in the order
+/// of `ifds`, followed by the SubIFDs, whose indices are given by `sub_ifds`.
+pub struct TiffReader<B> {
+    source: RangeSource<B>,
+    endianness: Endianness,
+    ifds: Vec<Ifd>,
+    /// The path to each addressable IFD: the index of a top-level IFD followed by the indices
+    /// of the SubIFDs leading to it.
+    paths: Vec<Vec<usize>>,
+    /// The layouts of the strips or tiles of the IFDs, built when they are first needed since
+    /// they copy the offsets and byte counts of every chunk.
+    layouts: Vec<OnceLock<Option<Arc<ChunkLayout>>>>,
+    options: TiffOptions,
+    cache: HashMap<(usize, usize), Arc<Vec<u8>>>,
+    cache_order: VecDeque<(usize, usize)>,
+}
+
+impl TiffReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(name: P) -> Result<Self, TiffParserError> {
+        Self::new(BufReader::new(File::open(name)?))
+    }
+}
+
+impl<B: ByteSource> TiffReader<B> {
+    pub fn new(source: B) -> Result<Self, TiffParserError> {
+        Self::with_options(source, TiffOptions::default())
+    }
+
+    pub fn with_options(source: B, options: TiffOptions) -> Result<Self, TiffParserError> {
+        let mut source = RangeSource::new(source)?;
+        let (endianness, ifds) = read_ifds(&mut source)?;
+        let mut paths = (0..ifds.len()).map(|index| vec![index]).collect::<Vec<_>>();
+        // the SubIFDs of the IFDs added to the list are appended to it in turn
+        let mut next = 0;
+        while next < paths.len() {
+            let path = paths[next].clone();
+            for index in 0..ifd_at(&ifds, &path).sub_ifds.len() {
+                paths.push([path.as_slice(), &[index]].concat());
+            }
+            next += 1;
+        }
+        Ok(Self {
+            source,
+            endianness,
+            layouts: paths.iter().map(|_| OnceLock::new()).collect(),
+            paths,
+            ifds,
+            options,
+            cache: HashMap::new(),
+            cache_order: VecDeque::new(),
+        })
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// The top-level IFDs of the file, without any image data.
+    pub fn ifds(&self) -> &[Ifd] {
+        &self.ifds
+    }
+
+    /// Returns the IFD with the given index, which may be a SubIFD.
+    pub fn ifd(&self, ifd: usize) -> Option<&Ifd> {
+        self.paths.get(ifd).map(|path| ifd_at(&self.ifds, path))
+    }
+
+    /// Returns the indices of the SubIFDs of the given IFD.
+    pub fn sub_ifds(&self, ifd: usize) -> Vec<usize> {
+        let Some(parent) = self.paths.get(ifd) else {
+            return vec![];
+        };
+        self.paths
+            .iter()
+            .enumerate()
+            .filter(|(_, path)| path.len() == parent.len() + 1 && path.starts_with(parent))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Returns the number of strips or tiles of the image in the given IFD.
+    pub fn num_chunks(&self, ifd: usize) -> Result<usize, TiffParserError> {
+        Ok(self.layout(ifd)?.map_or(0, |layout| layout.num_chunks()))
+    }
+
+    /// Fetches and decodes a single strip or tile of the image in the given IFD. Its samples
+    /// are stored row by row, and rows of tiles extending beyond the edge of the image are not
+    /// cropped. If the bands are stored in separate planes, the chunk only holds one band.
+    pub fn read_chunk(
+        &mut self,
+        ifd: usize,
+        index: usize,
+    ) -> Result<RasterBuffer, TiffParserError> {
+        let layout = self
+            .layout(ifd)?
+            .ok_or(TiffParserError::MissingChunk(index))?;
+        let chunks = self.fetch_chunks(ifd, &layout, &[index])?;
+        ifd_at(&self.ifds, &self.paths[ifd]).raster_buffer(self.endianness, &chunks[&index])
+    }
+
+    /// Fetches and decodes the whole image in the given IFD, or returns `None` if the IFD has
+    /// no image data.
+    pub fn read_image(&mut self, ifd: usize) -> Result<Option<RasterBuffer>, TiffParserError> {
+        match self.layout(ifd)? {
+            Some(layout) => self
+                .read_window(ifd, 0, 0, layout.image_width, layout.image_length)
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Decodes the pixels of the image in the given IFD within the window of `width` by
+    /// `height` pixels whose top-left corner is at column `x_off` and row `y_off`, only
+    /// fetching the strips or tiles intersecting it.
+    pub fn read_window(
+        &mut self,
+        ifd: usize,
+        x_off: usize,
+        y_off: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<RasterBuffer, TiffParserError> {
+        self.read_samples(ifd, None, (x_off, y_off, width, height))
+    }
+
+    /// Like `read_window`, but only decodes the samples of the given band. If the bands are
+    /// stored in separate planes, the strips or tiles of the other bands aren't fetched.
+    pub fn read_band_window(
+        &mut self,
+        ifd: usize,
+        band: usize,
+        x_off: usize,
+        y_off: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<RasterBuffer, TiffParserError> {
+        self.read_samples(ifd, Some(band), (x_off, y_off, width, height))
+    }
+
+    pub fn into_inner(self) -> B {
+        self.source.source
+    }
+
+    /// Returns the decoded bytes of the given strips or tiles, taking them from the cache if
+    /// possible and fetching the rest with as few reads as possible.
+    fn fetch_chunks(
+        &mut self,
+        ifd: usize,
+        layout: &ChunkLayout,
+        indices: &[usize],
+    ) -> Result<HashMap<usize, Arc<Vec<u8>>>, TiffParserError> {
+        let mut chunks = HashMap::new();
+        let mut missing = vec![];
+        for &index in indices {
+            if index >= layout.num_chunks() {
+                return Err(TiffParserError::MissingChunk(index));
+            }
+            match self.cache.get(&(ifd, index)) {
+                Some(chunk) => {
+                    chunks.insert(index, chunk.clone());
+                }
+                None => missing.push(index),
+            }
+        }
+        missing.sort_by_key(|index| (layout.offsets[*index], *index));
+        missing.dedup();
+
+        let mut start = 0;
+        while start < missing.len() {
+            // extend the range as long as the next chunk starts close enough to its end, keeping
+            // track of the chunk ending last in case the range goes beyond the end of the file
+            let chunk_end =
+                |index: usize| layout.offsets[index].saturating_add(layout.byte_counts[index]);
+            let range_start = layout.offsets[missing[start]];
+            let mut range_end = chunk_end(missing[start]);
+            let mut last = missing[start];
+            let mut end = start + 1;
+            while end < missing.len()
+                && layout.offsets[missing[end]] <= range_end.saturating_add(MAX_RANGE_GAP)
+            {
+                if chunk_end(missing[end]) > range_end {
+                    range_end = chunk_end(missing[end]);
+                    last = missing[end];
+                }
+                end += 1;
+            }
+
+            let bytes = match self
+                .source
+                .read_bytes(range_start as usize, (range_end - range_start) as usize)
+            {
+                Err(TiffParserError::OutOfBounds(..)) => {
+                    return Err(TiffParserError::ChunkOutOfBounds(
+                        last,
+                        layout.offsets[last],
+                        layout.byte_counts[last],
+                    ));
+                }
+                bytes => bytes?.into_owned(),
+            };
+            let mut window = Window {
+                offset: range_start as usize,
+                bytes: &bytes,
+            };
+            for &index in &missing[start..end] {
+                let chunk = Arc::new(ifd_at(&self.ifds, &self.paths[ifd]).read_chunk(
+                    self.endianness,
+                    &mut window,
+                    &self.options.decompressors,
+                    layout,
+                    index,
+                )?);
+                self.cache_chunk(ifd, index, chunk.clone());
+                chunks.insert(index, chunk);
+            }
+            start = end;
+        }
+        Ok(chunks)
+    }
+
+    fn read_samples(
+        &mut self,
+        ifd: usize,
+        band: Option<usize>,
+        window: (usize, usize, usize, usize),
+    ) -> Result<RasterBuffer, TiffParserError> {
+        let (x_off, y_off, width, height) = window;
+        let layout = self.layout(ifd)?.ok_or(TiffParserError::MissingChunk(0))?;
+        if x_off
+            .checked_add(width)
+            .is_none_or(|end| end > layout.image_width)
+            || y_off
+                .checked_add(height)
+                .is_none_or(|end| end > layout.image_length)
+        {
+            return Err(TiffParserError::InvalidWindow(x_off, y_off, width, height));
+        }
+        let indices = layout.window_chunks(band, x_off, y_off, width, height)?;
+        let chunks = self.fetch_chunks(ifd, &layout, &indices)?;
+        let ifd = ifd_at(&self.ifds, &self.paths[ifd]);
+        let bytes = ifd.window_bytes(
+            &layout,
+            |index| chunks.get(&index).map(|chunk| chunk.as_slice()),
+            band,
+            window,
+        )?;
+        ifd.raster_buffer(self.endianness, &bytes)
+    }
+
+    fn cache_chunk(&mut self, ifd: usize, index: usize, chunk: Arc<Vec<u8>>) {
+        if self.options.chunk_cache_capacity == 0 {
+            return;
+        }
+        if self.cache.len() >= self.options.chunk_cache_capacity {
+            if let Some(oldest) = self.cache_order.pop_front() {
+                self.cache.remove(&oldest);
+            }
+        }
+        self.cache.insert((ifd, index), chunk);
+        self.cache_order.push_back((ifd, index));
+    }
+
+    fn layout(&self, ifd: usize) -> Result<Option<Arc<ChunkLayout>>, TiffParserError> {
+        let cell = self
+            .layouts
+            .get(ifd)
+            .ok_or(TiffParserError::MissingIfd(ifd))?;
+        if let Some(layout) = cell.get() {
+            return Ok(layout.clone());
+        }
+        let layout = ifd_at(&self.ifds, &self.paths[ifd])
+            .chunk_layout()?
+            .map(Arc::new);
+        Ok(cell.get_or_init(|| layout).clone())
+    }
+}
+
+/// Returns the IFD at the end of the given path.
+fn ifd_at<'a>(ifds: &'a [Ifd], path: &[usize]) -> &'a Ifd {
+    path[1..]
+        .iter()
+        .fold(&ifds[path[0]], |ifd, &index| &ifd.sub_ifds[index])
+}
+
+impl<B> fmt::Debug for TiffReader<B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TiffReader")
+            .field("endianness", &self.endianness)
+            .field("ifds", &self.ifds)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, sync::Mutex};
+
+    use super::*;
+    use crate::parser::{
+        raster_buffer::RasterValue,
+        test_tiff::{write_tiff, TestIfd},
+    };
+
+    /// A source recording the ranges read from it.
+    struct RecordingSource {
+        bytes: Vec<u8>,
+        reads: Arc<Mutex<Vec<(u64, usize)>>>,
+    }
+
+    impl ByteSource for RecordingSource {
+        fn read_range(&mut self, offset: u64, len: usize) -> Result<Vec<u8>, TiffParserError> {
+            self.reads.lock().unwrap().push((offset, len));
+            let start = (offset as usize).min(self.bytes.len());
+            let end = start.saturating_add(len).min(self.bytes.len());
+            Ok(self.bytes[start..end].to_vec())
+        }
+    }
+
+    #[test]
+    fn adjacent_strips_are_read_at_once() {
+        // strips of 4096 bytes, most of them beyond the header read when the reader is created
+        let strips = (0..8).map(|strip| vec![strip; 4096]).collect();
+        let ifd = TestIfd::stripped(4096, 8, 1).strips(strips);
+        let reads = Arc::new(Mutex::new(vec![]));
+        let source = RecordingSource {
+            bytes: write_tiff(&[ifd]),
+            reads: reads.clone(),
+        };
+        let mut reader = TiffReader::new(source).unwrap();
+        reads.lock().unwrap().clear();
+
+        let window = reader.read_window(0, 0, 5, 4096, 3).unwrap();
+        assert_eq!(reads.lock().unwrap().as_slice(), [(8 + 5 * 4096, 3 * 4096)]);
+        assert_eq!(window.get(0), Some(RasterValue::U8(5)));
+        assert_eq!(window.get(3 * 4096 - 1), Some(RasterValue::U8(7)));
+
+        // the strips are now cached
+        reader.read_window(0, 0, 6, 4096, 2).unwrap();
+        assert_eq!(reads.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn layouts_are_built_once() {
+        let ifd = TestIfd::stripped(2, 2, 1).strips(vec![vec![1, 2], vec![3, 4]]);
+        let mut reader = TiffReader::new(Cursor::new(write_tiff(&[ifd]))).unwrap();
+        let layout = reader.layout(0).unwrap().unwrap();
+        reader.read_window(0, 0, 1, 2, 1).unwrap();
+        assert!(Arc::ptr_eq(&layout, &reader.layout(0).unwrap().unwrap()));
+        assert!(matches!(
+            reader.layout(1),
+            Err(TiffParserError::MissingIfd(1))
+        ));
+    }
+
+    #[test]
+    fn sub_ifds_are_addressable() {
+        // two top-level IFDs, the first with a SubIFD which has one of its own
+        let first = TestIfd::stripped(1, 1, 1).strips(vec![vec![1]]).sub_ifd(
+            TestIfd::stripped(2, 1, 1)
+                .strips(vec![vec![2, 3]])
+                .sub_ifd(TestIfd::stripped(1, 1, 1).strips(vec![vec![4]])),
+        );
+        let second = TestIfd::stripped(1, 1, 1).strips(vec![vec![5]]);
+        let mut reader = TiffReader::new(Cursor::new(write_tiff(&[first, second]))).unwrap();
+        assert_eq!(reader.ifds().len(), 2);
+        assert_eq!(reader.sub_ifds(0), vec![2]);
+        assert_eq!(reader.sub_ifds(1), Vec::<usize>::new());
+        assert_eq!(reader.sub_ifds(2), vec![3]);
+        assert_eq!(reader.sub_ifds(4), Vec::<usize>::new());
+        assert_eq!(reader.ifd(2).unwrap().image_width(), Some(2));
+        assert!(reader.ifd(4).is_none());
+
+        assert_eq!(
+            reader.read_image(1).unwrap(),
+            Some(RasterBuffer::U8(vec![5]))
+        );
+        assert_eq!(
+            reader.read_window(2, 1, 0, 1, 1).unwrap(),
+            RasterBuffer::U8(vec![3])
+        );
+        assert_eq!(reader.read_chunk(3, 0).unwrap(), RasterBuffer::U8(vec![4]));
+        assert!(matches!(
+            reader.read_image(4),
+            Err(TiffParserError::MissingIfd(4))
+        ));
+    }
+}
@@ -14,8 +14,12 @@ pub enum Value {
     Srationals(Vec<(i32, i32)>),
     Floats(Vec<f32>),
     Doubles(Vec<f64>),
+    Ifds(Vec<u32>),
     Ascii(String),
     Undefined(Vec<u8>),
+    Longs8(Vec<u64>),
+    Slongs8(Vec<i64>),
+    Ifds8(Vec<u64>),
 }
 
 fn try_tuple<T1, T2, E>(res1: Result<T1, E>, res2: Result<T2, E>) -> Result<(T1, T2), E> {
@@ -73,14 +77,10 @@ impl Value {
                     .collect();
                 Ok(Value::Srationals(vals?))
             }
-            Field::Ascii => {
-                let bytes = buf.to_vec();
-                let len = bytes.len();
-                if bytes[len - 1] != 0 {
-                    return Err(TiffParserError::StringNotNullTerminated(bytes));
-                }
-                Ok(Value::Ascii(String::from_utf8(bytes[0..len - 1].to_vec())?))
-            }
+            Field::Ascii => match buf.split_last() {
+                Some((0, string)) => Ok(Value::Ascii(String::from_utf8(string.to_vec())?)),
+                _ => Err(TiffParserError::StringNotNullTerminated(buf.to_vec())),
+            },
             Field::Undefined => {
                 let bytes = buf.to_vec();
                 Ok(Value::Undefined(bytes))
@@ -99,19 +99,39 @@ impl Value {
                     .collect();
                 Ok(Value::Doubles(vals?))
             }
+            Field::Ifd => {
+                let vals: Result<Vec<_>, _> =
+                    buf.chunks(4).map(|b| endianness.read_u32(b)).collect();
+                Ok(Value::Ifds(vals?))
+            }
+            Field::Long8 => {
+                let vals: Result<Vec<_>, _> =
+                    buf.chunks(8).map(|b| endianness.read_u64(b)).collect();
+                Ok(Value::Longs8(vals?))
+            }
+            Field::Slong8 => {
+                let vals: Result<Vec<_>, _> =
+                    buf.chunks(8).map(|b| endianness.read_i64(b)).collect();
+                Ok(Value::Slongs8(vals?))
+            }
+            Field::Ifd8 => {
+                let vals: Result<Vec<_>, _> =
+                    buf.chunks(8).map(|b| endianness.read_u64(b)).collect();
+                Ok(Value::Ifds8(vals?))
+            }
         }
     }
 
     pub fn short(&self) -> Result<u16, TiffParserError> {
         match self {
-            Value::Shorts(vals) => Ok(vals[0]),
+            Value::Shorts(vals) if !vals.is_empty() => Ok(vals[0]),
             val => Err(TiffParserError::InvalidValue(val.clone(), "expected short")),
         }
     }
 
     pub fn long(&self) -> Result<u32, TiffParserError> {
         match self {
-            Value::Longs(vals) => Ok(vals[0]),
+            Value::Longs(vals) if !vals.is_empty() => Ok(vals[0]),
             val => Err(TiffParserError::InvalidValue(val.clone(), "expected long")),
         }
     }
@@ -132,6 +152,69 @@ impl Value {
             val => Err(TiffParserError::InvalidValue(val.clone(), "expected longs")),
         }
     }
+
+    pub fn doubles(&self) -> Result<&[f64], TiffParserError> {
+        match self {
+            Value::Doubles(vals) => Ok(vals),
+            val => Err(TiffParserError::InvalidValue(
+                val.clone(),
+                "expected doubles",
+            )),
+        }
+    }
+
+    pub fn ascii(&self) -> Result<&str, TiffParserError> {
+        match self {
+            Value::Ascii(string) => Ok(string),
+            val => Err(TiffParserError::InvalidValue(val.clone(), "expected ascii")),
+        }
+    }
+
+    pub fn undefined(&self) -> Result<&[u8], TiffParserError> {
+        match self {
+            Value::Undefined(vals) => Ok(vals),
+            val => Err(TiffParserError::InvalidValue(
+                val.clone(),
+                "expected undefined",
+            )),
+        }
+    }
+
+    /// Returns the first value of an unsigned integer field of any size. Dimensions may be
+    /// stored as SHORT or LONG, and offsets as LONG in classic TIFF files and LONG8 in BigTIFF
+    /// files, although some writers use other sizes.
+    pub fn as_u64(&self) -> Result<u64, TiffParserError> {
+        let val = match self {
+            Value::Bytes(vals) => vals.first().map(|val| *val as u64),
+            Value::Shorts(vals) => vals.first().map(|val| *val as u64),
+            Value::Longs(vals) | Value::Ifds(vals) => vals.first().map(|val| *val as u64),
+            Value::Longs8(vals) | Value::Ifds8(vals) => vals.first().copied(),
+            val => {
+                return Err(TiffParserError::InvalidValue(
+                    val.clone(),
+                    "expected an unsigned integer",
+                ))
+            }
+        };
+        val.ok_or_else(|| TiffParserError::InvalidValue(self.clone(), "expected a value"))
+    }
+
+    /// Returns the values of an unsigned integer field of any size, such as StripOffsets or
+    /// TileByteCounts.
+    pub fn as_u64s(&self) -> Result<Vec<u64>, TiffParserError> {
+        match self {
+            Value::Bytes(vals) => Ok(vals.iter().map(|val| *val as u64).collect()),
+            Value::Shorts(vals) => Ok(vals.iter().map(|val| *val as u64).collect()),
+            Value::Longs(vals) | Value::Ifds(vals) => {
+                Ok(vals.iter().map(|val| *val as u64).collect())
+            }
+            Value::Longs8(vals) | Value::Ifds8(vals) => Ok(vals.clone()),
+            val => Err(TiffParserError::InvalidValue(
+                val.clone(),
+                "expected unsigned integers",
+            )),
+        }
+    }
 }
 
 const MAX_LEN: usize = 226;
@@ -239,6 +322,42 @@ impl fmt::Debug for Value {
                         .finish()
                 }
             }
+            Value::Ifds(vals) => {
+                if vals.len() < MAX_LEN {
+                    f.debug_tuple("Ifds").field(vals).finish()
+                } else {
+                    f.debug_tuple("Ifds")
+                        .field(&format_args!("{} values", vals.len()))
+                        .finish()
+                }
+            }
+            Value::Longs8(vals) => {
+                if vals.len() < MAX_LEN {
+                    f.debug_tuple("Longs8").field(vals).finish()
+                } else {
+                    f.debug_tuple("Longs8")
+                        .field(&format_args!("{} values", vals.len()))
+                        .finish()
+                }
+            }
+            Value::Slongs8(vals) => {
+                if vals.len() < MAX_LEN {
+                    f.debug_tuple("Slongs8").field(vals).finish()
+                } else {
+                    f.debug_tuple("Slongs8")
+                        .field(&format_args!("{} values", vals.len()))
+                        .finish()
+                }
+            }
+            Value::Ifds8(vals) => {
+                if vals.len() < MAX_LEN {
+                    f.debug_tuple("Ifds8").field(vals).finish()
+                } else {
+                    f.debug_tuple("Ifds8")
+                        .field(&format_args!("{} values", vals.len()))
+                        .finish()
+                }
+            }
         }
     }
 }
@@ -0,0 +1,55 @@
+use super::{endianness::Endianness, TiffParserError};
+
+/// Distinguishes classic TIFF files from BigTIFF files, which use 64-bit offsets and counts.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum TiffVariant {
+    Classic,
+    Big,
+}
+
+impl TiffVariant {
+    /// The size of offsets and counts, which is also the size of an IFD entry's inline value.
+    pub(super) fn offset_size(&self) -> usize {
+        match self {
+            TiffVariant::Classic => 4,
+            TiffVariant::Big => 8,
+        }
+    }
+
+    /// The size of the number of entries at the start of an IFD.
+    pub(super) fn entry_count_size(&self) -> usize {
+        match self {
+            TiffVariant::Classic => 2,
+            TiffVariant::Big => 8,
+        }
+    }
+
+    pub(super) fn entry_size(&self) -> usize {
+        match self {
+            TiffVariant::Classic => 12,
+            TiffVariant::Big => 20,
+        }
+    }
+
+    pub(super) fn read_offset(
+        &self,
+        endianness: Endianness,
+        buf: &[u8],
+    ) -> Result<usize, TiffParserError> {
+        match self {
+            TiffVariant::Classic => Ok(endianness.read_u32(buf)? as usize),
+            TiffVariant::Big => Ok(endianness.read_u64(buf)? as usize),
+        }
+    }
+
+    pub(super) fn read_entry_count(
+        &self,
+        endianness: Endianness,
+        buf: &[u8],
+    ) -> Result<usize, TiffParserError> {
+        match self {
+            TiffVariant::Classic => Ok(endianness.read_u16(buf)? as usize),
+            TiffVariant::Big => Ok(endianness.read_u64(buf)? as usize),
+        }
+    }
+}
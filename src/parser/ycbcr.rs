@@ -0,0 +1,143 @@
+use super::{ifd::Ifd, tags::*, value::Value, TiffParserError};
+
+/// The parameters needed to convert YCbCr data, possibly with subsampled chroma, to RGB.
+pub(super) struct YCbCrConverter {
+    subsampling: (usize, usize),
+    coefficients: [f64; 3],
+    reference_black_white: [f64; 6],
+}
+
+impl YCbCrConverter {
+    pub(super) fn new(ifd: &Ifd) -> Result<Self, TiffParserError> {
+        let subsampling = match ifd.get_value(TAG_YCBCR_SUBSAMPLING) {
+            Ok(value) => match value.shorts()? {
+                [h @ (1 | 2 | 4), v @ (1 | 2 | 4)] if v <= h => (*h as usize, *v as usize),
+                _ => {
+                    return Err(TiffParserError::InvalidValue(
+                        value.clone(),
+                        "invalid YCbCr subsampling",
+                    ));
+                }
+            },
+            Err(_) => (2, 2),
+        };
+        let coefficients = match ifd.get_value(TAG_YCBCR_COEFFICIENTS) {
+            Ok(Value::Rationals(vals)) if vals.len() == 3 => {
+                [0, 1, 2].map(|i| vals[i].0 as f64 / vals[i].1 as f64)
+            }
+            Ok(value) => {
+                return Err(TiffParserError::InvalidValue(
+                    value.clone(),
+                    "expected 3 rationals",
+                ));
+            }
+            Err(_) => [0.299, 0.587, 0.114],
+        };
+        let reference_black_white = match ifd.get_value(TAG_REFERENCE_BLACK_WHITE) {
+            Ok(Value::Rationals(vals)) if vals.len() == 6 => {
+                [0, 1, 2, 3, 4, 5].map(|i| vals[i].0 as f64 / vals[i].1 as f64)
+            }
+            Ok(value) => {
+                return Err(TiffParserError::InvalidValue(
+                    value.clone(),
+                    "expected 6 rationals",
+                ));
+            }
+            Err(_) => [0.0, 255.0, 128.0, 255.0, 128.0, 255.0],
+        };
+        Ok(Self {
+            subsampling,
+            coefficients,
+            reference_black_white,
+        })
+    }
+
+    /// The size of the YCbCr data of a chunk with `rows` rows of `width` pixels, or `None` if it
+    /// overflows. Chroma subsampling is applied to blocks of pixels, so partial blocks at the
+    /// edges are padded.
+    pub(super) fn encoded_size(&self, width: usize, rows: usize) -> Option<usize> {
+        let (h, v) = self.subsampling;
+        width
+            .div_ceil(h)
+            .checked_mul(rows.div_ceil(v))?
+            .checked_mul(h * v + 2)
+    }
+
+    /// Converts 8-bit YCbCr data, stored as blocks of luma samples followed by the two chroma
+    /// samples, to interleaved 8-bit RGB.
+    pub(super) fn to_rgb(&self, data: &[u8], width: usize, rows: usize) -> Vec<u8> {
+        let (h, v) = self.subsampling;
+        let blocks_per_row = width.div_ceil(h);
+        let block_size = h * v + 2;
+        let mut result = vec![0; width * rows * 3];
+        for (index, block) in data.chunks_exact(block_size).enumerate() {
+            let (block_col, block_row) = (index % blocks_per_row, index / blocks_per_row);
+            let (cb, cr) = (block[h * v], block[h * v + 1]);
+            for dy in 0..v {
+                for dx in 0..h {
+                    let (x, y) = (block_col * h + dx, block_row * v + dy);
+                    if x >= width || y >= rows {
+                        continue;
+                    }
+                    let rgb = self.convert(block[dy * h + dx], cb, cr);
+                    result[(y * width + x) * 3..][..3].copy_from_slice(&rgb);
+                }
+            }
+        }
+        result
+    }
+
+    fn convert(&self, y: u8, cb: u8, cr: u8) -> [u8; 3] {
+        let [luma_red, luma_green, luma_blue] = self.coefficients;
+        let [y_black, y_white, cb_black, cb_white, cr_black, cr_white] = self.reference_black_white;
+        let y = (y as f64 - y_black) * 255.0 / (y_white - y_black);
+        let cb = (cb as f64 - cb_black) * 127.0 / (cb_white - cb_black);
+        let cr = (cr as f64 - cr_black) * 127.0 / (cr_white - cr_black);
+        let r = cr * (2.0 - 2.0 * luma_red) + y;
+        let b = cb * (2.0 - 2.0 * luma_blue) + y;
+        let g = (y - luma_blue * b - luma_red * r) / luma_green;
+        [r, g, b].map(|val| val.round().clamp(0.0, 255.0) as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn converter(subsampling: (usize, usize)) -> YCbCrConverter {
+        YCbCrConverter {
+            subsampling,
+            coefficients: [0.299, 0.587, 0.114],
+            reference_black_white: [0.0, 255.0, 128.0, 255.0, 128.0, 255.0],
+        }
+    }
+
+    #[test]
+    fn neutral_chroma_is_grey() {
+        let converter = converter((2, 1));
+        assert_eq!(
+            converter.to_rgb(&[100, 200, 128, 128], 2, 1),
+            [100, 100, 100, 200, 200, 200]
+        );
+    }
+
+    #[test]
+    fn primary_colors() {
+        let converter = converter((1, 1));
+        assert_eq!(converter.convert(76, 85, 255), [254, 0, 0]);
+        assert_eq!(converter.convert(150, 44, 21), [0, 255, 1]);
+        assert_eq!(converter.convert(29, 255, 107), [0, 0, 254]);
+    }
+
+    #[test]
+    fn partial_blocks_at_the_edges() {
+        // 3 by 1 pixels in blocks of 2 by 2, so the second block only has one pixel in the image
+        let converter = converter((2, 2));
+        assert_eq!(converter.encoded_size(3, 1), Some(12));
+        let data = [10, 20, 30, 40, 128, 128, 50, 60, 70, 80, 128, 128];
+        assert_eq!(
+            converter.to_rgb(&data, 3, 1),
+            [10, 10, 10, 20, 20, 20, 50, 50, 50]
+        );
+    }
+}